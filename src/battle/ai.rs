@@ -0,0 +1,168 @@
+use crate::{battle::*, characters::*};
+use rand::{seq::SliceRandom, Rng, RngCore};
+use std::cmp::Reverse;
+
+/// Decides what a character not controlled by the player does on its turn
+pub trait EnemyController {
+    /// Name the controller is picked by, see `enemy_controller`
+    fn name(&self) -> &'static str;
+
+    /// Picks the next action of `actor`, or `None` to let its turn pass by.
+    /// Any randomness must come from `rng` so battles can be replayed
+    fn choose(
+        &mut self,
+        state: &BattleState,
+        actor: CharaId,
+        rng: &mut dyn RngCore,
+    ) -> Option<QueuedAction>;
+}
+
+/// Every affordable action of the character, paired with its command
+fn usable_actions(chara: &Character) -> Vec<(Commands, &Action)> {
+    chara
+        .commands()
+        .into_iter()
+        .filter(|cmd| check_command(chara, *cmd).is_ok())
+        .flat_map(|cmd| {
+            chara.act_available[cmd as usize]
+                .iter()
+                .filter(|act| chara.can_afford(act))
+                .map(move |act| (cmd, act))
+        })
+        .collect()
+}
+
+/// Queues `action`, picking its target among the candidates with `pick` when
+/// it needs one
+fn queued(
+    state: &BattleState,
+    actor: CharaId,
+    command: Commands,
+    action: &Action,
+    pick: impl FnOnce(Vec<CharaId>) -> Option<CharaId>,
+) -> Option<QueuedAction> {
+    let to = if action.target.is_single() {
+        pick(state.target_candidates(actor, action))?
+    } else {
+        actor
+    };
+    Some(QueuedAction {
+        from: actor,
+        command,
+        action: action.clone(),
+        to,
+    })
+}
+
+/// Picks any affordable action against any valid target
+pub struct RandomController;
+impl EnemyController for RandomController {
+    fn name(&self) -> &'static str {
+        "random"
+    }
+
+    fn choose(
+        &mut self,
+        state: &BattleState,
+        actor: CharaId,
+        rng: &mut dyn RngCore,
+    ) -> Option<QueuedAction> {
+        let (command, action) = *usable_actions(state.chara(actor)).choose(rng)?;
+        queued(state, actor, command, action, |targets| {
+            targets.choose(rng).copied()
+        })
+    }
+}
+
+/// Focuses the weakest opponent with its strongest action and heals allies
+/// whose health drops under `low_health` (0.0 to 1.0). When it's the one that
+/// low and has no heal, it guards on `guard_chance` of its turns
+pub struct HeuristicController {
+    pub low_health: f32,
+    pub guard_chance: f32,
+}
+impl Default for HeuristicController {
+    fn default() -> Self {
+        HeuristicController {
+            low_health: 0.3,
+            guard_chance: 0.25,
+        }
+    }
+}
+impl EnemyController for HeuristicController {
+    fn name(&self) -> &'static str {
+        "heuristic"
+    }
+
+    fn choose(
+        &mut self,
+        state: &BattleState,
+        actor: CharaId,
+        rng: &mut dyn RngCore,
+    ) -> Option<QueuedAction> {
+        let chara = state.chara(actor);
+        let mut actions = usable_actions(chara);
+        let is_low = |id: &CharaId| {
+            let c = state.chara(*id);
+            (c.health as f32 / c.max_health.max(1) as f32) < self.low_health
+        };
+        let weakest =
+            |targets: Vec<CharaId>| targets.into_iter().min_by_key(|id| state.chara(*id).health);
+
+        let heals = actions
+            .iter()
+            .filter(|(_, a)| a.heals() && a.target.is_single());
+        for (command, action) in heals {
+            let heal = queued(state, actor, *command, action, |targets| {
+                weakest(targets.into_iter().filter(is_low).collect())
+            });
+            if heal.is_some() {
+                return heal;
+            }
+        }
+        if is_low(&actor) && rng.gen::<f32>() < self.guard_chance {
+            if let Some((command, action)) = actions.iter().find(|(c, _)| *c == Commands::Defend) {
+                return queued(state, actor, *command, action, |targets| {
+                    targets.first().copied()
+                });
+            }
+        }
+
+        // Strongest first and guarding last, skipping the ones without a valid target
+        actions.sort_by_key(|(c, a)| (*c == Commands::Defend, Reverse(a.damage)));
+        actions
+            .iter()
+            .find_map(|(command, action)| queued(state, actor, *command, action, weakest))
+    }
+}
+
+/// Builds a controller from its name, as given in the command line or a replay
+pub fn enemy_controller(name: &str) -> Option<Box<dyn EnemyController>> {
+    match name {
+        "random" => Some(Box::new(RandomController)),
+        "heuristic" => Some(Box::new(HeuristicController::default())),
+        _ => None,
+    }
+}
+
+/// Lets the controller queue an action for every ready enemy
+pub fn run_enemy_ai(ctrl: &mut dyn EnemyController, state: &mut BattleState) {
+    run_ai(ctrl, state, Side::Enemy);
+}
+
+/// Lets the controller queue an action for every ready character of `side`
+pub fn run_ai(ctrl: &mut dyn EnemyController, state: &mut BattleState, side: Side) {
+    // The controller only gets to read the battle, so the rng is lent apart
+    let mut rng = state.rng.clone();
+    for index in 0..state.party(side).len() {
+        let actor = CharaId { side, index };
+        if !state.chara(actor).is_ready() {
+            continue;
+        }
+        if let Some(act) = ctrl.choose(state, actor, &mut rng) {
+            // The controller only picks affordable actions of ready characters
+            let _ = state.push_action(act);
+        }
+    }
+    state.rng = rng;
+}
@@ -0,0 +1,280 @@
+use crate::characters::*;
+use rand::Rng;
+
+const CRIT_MULTIPLIER: f32 = 1.5;
+
+/// How an action rolled against a single target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hit {
+    Landed,
+    Crit,
+    /// Not accurate enough to land
+    Missed,
+    /// Would have landed but the target dodged it
+    Evaded,
+}
+
+/// What happened to a single target hit by an action
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DamageResult {
+    /// Health actually removed from the target
+    pub amount: u16,
+    /// Damage left over after the target reached 0 health
+    pub overkill: u16,
+    pub crit: bool,
+    /// Set whenever nothing landed, evaded or not
+    pub miss: bool,
+    pub evaded: bool,
+    /// Affinity of the target to the element of the action, in which case
+    /// `amount` is the health it recovered if it absorbs it
+    pub affinity: Option<Affinity>,
+}
+impl DamageResult {
+    /// Health the target lost, nothing if it absorbed the damage
+    pub fn dealt(&self) -> u16 {
+        if self.affinity == Some(Affinity::Absorb) {
+            0
+        } else {
+            self.amount
+        }
+    }
+}
+
+/// Rolls the raw damage of `action` before it touches the target's health.
+/// It lands with a chance of its accuracy minus the defender's evasion, and
+/// crits with the attacker's crit chance, both growing with hope.
+///
+/// dmg = act_damage + tu_attack * (rand * tu_hope) - enemy_defense * (rand * enemy_hope) - guard
+pub fn roll_damage<R: Rng>(
+    rng: &mut R,
+    attacker: &Stats,
+    defender: &Stats,
+    guard: u16,
+    action: &Action,
+) -> (u16, Hit) {
+    let roll = rng.gen::<f32>();
+    if roll >= action.accuracy {
+        return (0, Hit::Missed);
+    }
+    if roll >= action.accuracy - defender.evasion() {
+        return (0, Hit::Evaded);
+    }
+
+    let attack = attacker.attack as f32 * (rng.gen::<f32>() * attacker.hope as f32);
+    let defense = defender.defense as f32 * (rng.gen::<f32>() * defender.hope as f32);
+    let mut dmg = (action.damage as f32 + attack - defense - guard as f32).max(0.0);
+
+    let hit = if rng.gen::<f32>() < attacker.crit_chance() {
+        dmg *= CRIT_MULTIPLIER;
+        Hit::Crit
+    } else {
+        Hit::Landed
+    };
+    (dmg.round().min(u16::MAX as f32) as u16, hit)
+}
+
+/// Removes `dmg` from the target, clamped to its health range
+pub fn apply_damage(target: &mut Character, dmg: u16) -> (u16, u16) {
+    let health = target.health.min(target.max_health);
+    let amount = dmg.min(health);
+    target.health = health - amount;
+    (amount, dmg - amount)
+}
+
+pub fn resolve_damage<R: Rng>(
+    rng: &mut R,
+    attacker: &Stats,
+    target: &mut Character,
+    action: &Action,
+) -> DamageResult {
+    let guard = target.guard.map_or(0, |g| g.reduction);
+    let defender = target.effective_stats();
+    let (dmg, hit) = roll_damage(rng, attacker, &defender, guard, action);
+    let crit = hit == Hit::Crit;
+    let evaded = hit == Hit::Evaded;
+    let miss = evaded || hit == Hit::Missed;
+    let affinity = action.affinity_of(target).filter(|_| !miss);
+    let dmg = affinity.map_or(dmg, |a| a.scale(dmg));
+
+    if affinity == Some(Affinity::Absorb) {
+        let amount = dmg.min(target.max_health.saturating_sub(target.health));
+        target.health += amount;
+        return DamageResult {
+            amount,
+            crit,
+            affinity,
+            ..Default::default()
+        };
+    }
+    let (amount, overkill) = apply_damage(target, dmg);
+    DamageResult {
+        amount,
+        overkill,
+        crit,
+        miss,
+        evaded,
+        affinity,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    /// Deals exactly `damage` when it lands, the attacker having no attack
+    fn action(damage: u16, accuracy: f32) -> Action {
+        Action {
+            damage,
+            accuracy,
+            ..Default::default()
+        }
+    }
+
+    fn stats(hope: u16) -> Stats {
+        Stats {
+            hope,
+            ..Default::default()
+        }
+    }
+
+    fn target(health: u16, hope: u16) -> Character {
+        Character {
+            health,
+            stats: stats(hope),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn overkill_is_what_goes_past_zero() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let mut chara = target(5, 0);
+
+        let result = resolve_damage(&mut rng, &stats(0), &mut chara, &action(20, 2.0));
+        assert_eq!(chara.health, 0);
+        assert_eq!(result.amount, 5);
+        let dmg = if result.crit { 30 } else { 20 };
+        assert_eq!(result.overkill, dmg - 5);
+    }
+
+    #[test]
+    fn defense_and_guard_reduce_the_damage() {
+        let mut rng = StdRng::seed_from_u64(6);
+        let defender = Stats {
+            defense: 2,
+            hope: 1,
+            ..Default::default()
+        };
+        for _ in 0..100 {
+            let (dmg, hit) = roll_damage(&mut rng, &stats(0), &defender, 3, &action(10, 2.0));
+            // Never more than the action minus the guard, crits aside
+            let cap = if hit == Hit::Crit { 11 } else { 7 };
+            assert!(dmg <= cap, "{} > {}", dmg, cap);
+        }
+    }
+
+    #[test]
+    fn never_lands_without_accuracy() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..100 {
+            let (dmg, hit) = roll_damage(&mut rng, &stats(0), &stats(0), 0, &action(10, 0.0));
+            assert_eq!((dmg, hit), (0, Hit::Missed));
+        }
+    }
+
+    #[test]
+    fn evasion_turns_hits_into_dodges() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let hits = (0..200)
+            .map(|_| {
+                roll_damage(
+                    &mut rng,
+                    &stats(0),
+                    &stats(100),
+                    0,
+                    &action(10, MAX_EVASION),
+                )
+                .1
+            })
+            .collect::<Vec<Hit>>();
+        // Whatever the accuracy lets through, the evasion takes away
+        assert!(hits.iter().all(|h| matches!(h, Hit::Missed | Hit::Evaded)));
+        assert!(hits.contains(&Hit::Evaded));
+    }
+
+    #[test]
+    fn crits_multiply_the_damage() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut crits = 0;
+        for _ in 0..200 {
+            let (dmg, hit) = roll_damage(&mut rng, &stats(100), &stats(0), 0, &action(10, 2.0));
+            match hit {
+                Hit::Crit => {
+                    assert_eq!(dmg, 15);
+                    crits += 1;
+                }
+                Hit::Landed => assert_eq!(dmg, 10),
+                _ => panic!("an accuracy of 2.0 can't miss"),
+            }
+        }
+        assert!(crits > 0);
+    }
+
+    #[test]
+    fn hope_raises_crits_and_evasion_up_to_a_cap() {
+        assert!(stats(5).crit_chance() > stats(0).crit_chance());
+        assert!(stats(5).evasion() > stats(0).evasion());
+        assert_eq!(stats(u16::MAX).crit_chance(), MAX_CRIT_CHANCE);
+        assert_eq!(stats(u16::MAX).evasion(), MAX_EVASION);
+    }
+
+    fn elemental(damage: u16) -> Action {
+        Action {
+            element: Some("ice".to_string()),
+            ..action(damage, 2.0)
+        }
+    }
+
+    #[test]
+    fn affinities_scale_the_damage() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for (affinity, dmg) in [
+            (Affinity::Weak, 30),
+            (Affinity::Resist, 10),
+            (Affinity::Immune, 0),
+        ] {
+            let mut chara = target(100, 0);
+            chara.affinities.insert("ice".to_string(), affinity);
+            let result = resolve_damage(&mut rng, &stats(0), &mut chara, &elemental(20));
+            let dmg = if result.crit { affinity.scale(30) } else { dmg };
+            assert_eq!(result.affinity, Some(affinity));
+            assert_eq!((result.amount, chara.health), (dmg, 100 - dmg));
+        }
+    }
+
+    #[test]
+    fn other_elements_deal_plain_damage() {
+        let mut rng = StdRng::seed_from_u64(8);
+        let mut chara = target(100, 0);
+        chara
+            .affinities
+            .insert("fire".to_string(), Affinity::Immune);
+        let result = resolve_damage(&mut rng, &stats(0), &mut chara, &elemental(20));
+        assert_eq!(result.affinity, None);
+        assert!(result.amount >= 20);
+    }
+
+    #[test]
+    fn absorbing_heals_the_target() {
+        let mut rng = StdRng::seed_from_u64(4);
+        let mut chara = target(50, 0);
+        chara.affinities.insert("ice".to_string(), Affinity::Absorb);
+
+        let result = resolve_damage(&mut rng, &stats(0), &mut chara, &elemental(20));
+        assert_eq!(result.affinity, Some(Affinity::Absorb));
+        assert_eq!(chara.health, 50 + result.amount);
+        assert!(result.amount >= 20);
+        assert_eq!(result.dealt(), 0);
+    }
+}
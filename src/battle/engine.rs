@@ -0,0 +1,227 @@
+use crate::{battle::*, characters::*, file_io::Encounter};
+
+/// Length of a single step of the battle, in seconds. Time always moves in
+/// whole steps so a replay goes through exactly the same states
+pub const STEP: f32 = 0.05;
+
+/// A battle that runs without any interface: actions get in through `submit`,
+/// time moves with `advance` and whatever happens comes out of `drain_events`
+pub struct Engine {
+    state: BattleState,
+    enemy_ai: Box<dyn EnemyController>,
+    /// Plays for the player party, as in simulations
+    player_ai: Option<Box<dyn EnemyController>>,
+    /// Log entries already handed out by `drain_events`
+    drained: usize,
+    /// Battle time left over from the last `advance`, always under a `STEP`
+    pending: f32,
+    /// Steps taken since the battle started
+    tick: u64,
+    /// The battle so far, ready to be saved and played again
+    replay: Replay,
+}
+impl Engine {
+    pub fn new(state: BattleState, enemy_ai: Box<dyn EnemyController>) -> Engine {
+        let replay = Replay {
+            title: String::new(),
+            ai: enemy_ai.name().to_string(),
+            seed: state.seed,
+            rules: state.rules.clone(),
+            player_party: state.player_party.clone(),
+            enemy_party: state.enemy_party.clone(),
+            inputs: vec![],
+        };
+        Engine {
+            state,
+            enemy_ai,
+            player_ai: None,
+            drained: 0,
+            pending: 0.0,
+            tick: 0,
+            replay,
+        }
+    }
+
+    pub fn from_encounter(
+        encounter: Encounter,
+        enemy_ai: Box<dyn EnemyController>,
+        seed: u64,
+    ) -> Engine {
+        let state = BattleState::new(
+            encounter.enemy_party,
+            encounter.player_party,
+            encounter.rules,
+            seed,
+        );
+        let mut engine = Engine::new(state, enemy_ai);
+        engine.replay.title = encounter.title;
+        engine
+    }
+
+    /// Starts the battle of a replay over, `None` if its enemy controller is unknown
+    pub fn from_replay(replay: &Replay) -> Option<Engine> {
+        let state = BattleState::new(
+            replay.enemy_party.clone(),
+            replay.player_party.clone(),
+            replay.rules.clone(),
+            replay.seed,
+        );
+        let mut engine = Engine::new(state, enemy_controller(&replay.ai)?);
+        engine.replay.title = replay.title.clone();
+        Some(engine)
+    }
+
+    /// Lets `player_ai` choose the actions of the player party too
+    pub fn set_player_ai(&mut self, player_ai: Box<dyn EnemyController>) {
+        self.player_ai = Some(player_ai);
+    }
+
+    pub fn state(&self) -> &BattleState {
+        &self.state
+    }
+
+    pub fn outcome(&self) -> Option<Outcome> {
+        self.state.outcome()
+    }
+
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    fn record(&mut self, input: Input) {
+        self.replay.inputs.push(RecordedInput {
+            tick: self.tick,
+            input,
+        });
+    }
+
+    /// Gives the engine an input, as recorded in a replay
+    pub fn apply(&mut self, input: Input) {
+        match input {
+            // Rejections end up in the battle log
+            Input::Submit(act) => {
+                let _ = self.submit(act);
+            }
+            Input::Flee => self.flee(),
+        }
+    }
+
+    /// Queues an action chosen from outside the engine
+    pub fn submit(&mut self, act: QueuedAction) -> Result<(), ActionError> {
+        self.record(Input::Submit(act.clone()));
+        self.state.push_action(act)
+    }
+
+    /// Checks whether `from` could use `action` right now, or act at all when
+    /// there's no action yet, logging the reason when it can't
+    pub fn validate(&mut self, from: CharaId, action: Option<&Action>) -> Result<(), ActionError> {
+        let chara = self.state.chara(from);
+        let res = match action {
+            Some(action) => check_action(chara, action),
+            None if chara.is_ko() => Err(ActionError::KnockedOut),
+            None if !chara.is_ready() => Err(ActionError::NotReady),
+            None => Ok(()),
+        };
+        if let Err(err) = res {
+            // Moving over the lists validates the same thing again, log it once
+            let rejection = (from, err, action.map(|a| (&a.id, &a.name)));
+            let repeated = matches!(
+                self.state.log.iter().next_back().map(|entry| &entry.event),
+                Some(BattleEvent::Rejected { from, action, err })
+                    if (*from, *err, action.as_ref().map(|a| (&a.id, &a.name))) == rejection
+            );
+            if !repeated {
+                self.state.log_event(BattleEvent::Rejected {
+                    from,
+                    action: action.cloned(),
+                    err,
+                });
+            }
+        }
+        res
+    }
+
+    pub fn flee(&mut self) {
+        self.record(Input::Flee);
+        self.state.flee();
+    }
+
+    /// Moves the battle `delta` seconds forward, in as many whole steps as fit
+    pub fn advance(&mut self, delta: f32) {
+        self.pending += delta;
+        while self.pending >= STEP {
+            self.pending -= STEP;
+            self.step();
+        }
+    }
+
+    /// Moves the battle a single `STEP` forward, letting the enemies act and
+    /// resolving every queued action. Does nothing once the battle is over
+    pub fn step(&mut self) {
+        if self.state.outcome().is_some() {
+            return;
+        }
+        self.state.update(STEP);
+        if let Some(player_ai) = &mut self.player_ai {
+            run_ai(player_ai.as_mut(), &mut self.state, Side::Player);
+        }
+        run_enemy_ai(self.enemy_ai.as_mut(), &mut self.state);
+        self.state.resolve_queue();
+        if self.state.outcome() == Some(Outcome::Victory) {
+            self.state.award_experience();
+        }
+        self.tick += 1;
+    }
+
+    /// Log entries added since the last call
+    pub fn drain_events(&mut self) -> Vec<LogEntry> {
+        let events = self.state.log.since(self.drained).cloned().collect();
+        self.drained = self.state.log.total();
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rejections(engine: &Engine) -> usize {
+        engine
+            .state()
+            .log
+            .iter()
+            .filter(|entry| matches!(entry.event, BattleEvent::Rejected { .. }))
+            .count()
+    }
+
+    #[test]
+    fn logs_repeated_rejections_once() {
+        let state = BattleState::new(
+            vec![Character::default()],
+            vec![Character::default()],
+            Rules::default(),
+            1,
+        );
+        let mut engine = Engine::new(state, Box::new(RandomController));
+        let from = CharaId {
+            side: Side::Player,
+            index: 0,
+        };
+        let action = Action::default();
+        for _ in 0..3 {
+            assert_eq!(
+                engine.validate(from, Some(&action)),
+                Err(ActionError::NotReady)
+            );
+        }
+        assert_eq!(rejections(&engine), 1);
+        // Anything else in between logs it again
+        let _ = engine.validate(from, None);
+        let _ = engine.validate(from, Some(&action));
+        assert_eq!(rejections(&engine), 3);
+    }
+}
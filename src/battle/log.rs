@@ -0,0 +1,130 @@
+use crate::{
+    battle::*,
+    characters::{Action, EffectOutcome, StatusKind},
+};
+use std::collections::{vec_deque, VecDeque};
+
+/// Entries kept before the oldest ones start being dropped
+pub const LOG_CAPACITY: usize = 200;
+
+#[derive(Debug, Clone)]
+pub enum BattleEvent {
+    /// Seed of the battle's rng, logged first so any battle can be reproduced
+    Seed(u64),
+    /// An action was carried out, `result` being `None` when it deals no damage
+    Action {
+        from: CharaId,
+        to: CharaId,
+        action: Action,
+        result: Option<DamageResult>,
+    },
+    /// An action was refused, either when chosen or when resolved
+    Rejected {
+        from: CharaId,
+        action: Option<Action>,
+        err: ActionError,
+    },
+    Ko(CharaId),
+    /// An effect of an action did something to its target
+    Effect {
+        id: CharaId,
+        outcome: EffectOutcome,
+    },
+    StatusApplied(CharaId, StatusKind),
+    /// Health lost to poison or recovered by regen
+    StatusTick {
+        id: CharaId,
+        kind: StatusKind,
+        amount: u16,
+    },
+    StatusRemoved(CharaId, StatusKind),
+    /// The Manif meter of the character got full
+    ManifReady(CharaId),
+    FleeFailed,
+    /// The character reached a new level after the victory
+    LevelUp {
+        id: CharaId,
+        level: u16,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    /// Seconds since the battle started
+    pub time: f32,
+    pub event: BattleEvent,
+}
+
+/// Everything that happened in the battle, bounded to `LOG_CAPACITY` entries
+#[derive(Debug, Default)]
+pub struct BattleLog {
+    entries: VecDeque<LogEntry>,
+    /// Entries ever pushed, including the dropped ones
+    total: usize,
+}
+impl BattleLog {
+    pub fn push(&mut self, time: f32, event: BattleEvent) {
+        if self.entries.len() >= LOG_CAPACITY {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(LogEntry { time, event });
+        self.total += 1;
+    }
+
+    pub fn iter(&self) -> vec_deque::Iter<'_, LogEntry> {
+        self.entries.iter()
+    }
+
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Entries pushed after the first `count` ones, as far as they are still kept
+    pub fn since(&self, count: usize) -> impl Iterator<Item = &LogEntry> {
+        let new = self.total.saturating_sub(count).min(self.entries.len());
+        self.entries.iter().skip(self.entries.len() - new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log_of(count: usize) -> BattleLog {
+        let mut log = BattleLog::default();
+        for i in 0..count {
+            log.push(i as f32, BattleEvent::Seed(i as u64));
+        }
+        log
+    }
+
+    fn seeds<'a>(entries: impl Iterator<Item = &'a LogEntry>) -> Vec<u64> {
+        entries
+            .map(|entry| match entry.event {
+                BattleEvent::Seed(seed) => seed,
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn hands_out_what_came_after() {
+        let log = log_of(5);
+        assert_eq!(seeds(log.since(3)), vec![3, 4]);
+        assert_eq!(seeds(log.since(5)), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn drops_the_oldest_past_capacity() {
+        let log = log_of(LOG_CAPACITY + 10);
+        assert_eq!(log.iter().count(), LOG_CAPACITY);
+        assert_eq!(log.total(), LOG_CAPACITY + 10);
+        assert_eq!(seeds(log.iter().take(1)), vec![10]);
+
+        let last = LOG_CAPACITY as u64 + 9;
+        assert_eq!(seeds(log.since(LOG_CAPACITY + 8)), vec![last - 1, last]);
+        // Entries already dropped can't be handed out anymore
+        assert_eq!(log.since(0).count(), LOG_CAPACITY);
+        assert_eq!(seeds(log.since(5).take(1)), vec![10]);
+    }
+}
@@ -0,0 +1,755 @@
+mod ai;
+mod damage;
+mod engine;
+mod log;
+mod replay;
+
+use crate::characters::*;
+pub use ai::*;
+pub use damage::*;
+pub use engine::*;
+pub use log::*;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+pub use replay::*;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Side {
+    Player,
+    Enemy,
+}
+impl Side {
+    pub fn opposite(self) -> Side {
+        match self {
+            Side::Player => Side::Enemy,
+            Side::Enemy => Side::Player,
+        }
+    }
+}
+
+/// Points to a character inside one of the parties of the battle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CharaId {
+    pub side: Side,
+    pub index: usize,
+}
+
+/// An action chosen by a character, waiting to be resolved
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedAction {
+    pub from: CharaId,
+    pub command: Commands,
+    pub action: Action,
+    pub to: CharaId,
+}
+
+/// How the battle ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Victory,
+    Defeat,
+    Fled,
+    TimeUp,
+}
+
+/// How many characters each party of an encounter may have
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PartyLimits {
+    pub min: usize,
+    pub max: usize,
+}
+impl Default for PartyLimits {
+    fn default() -> Self {
+        PartyLimits { min: 1, max: 6 }
+    }
+}
+impl PartyLimits {
+    pub fn allows(&self, size: usize) -> bool {
+        (self.min..=self.max).contains(&size)
+    }
+}
+
+/// Optional rules of an encounter
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Rules {
+    #[serde(default)]
+    pub flee_allowed: bool,
+    /// Seconds the player has to win the battle
+    #[serde(default)]
+    pub time_limit: Option<f32>,
+    #[serde(default)]
+    pub party_limits: PartyLimits,
+}
+
+/// Why an action was rejected, either when queued or when resolved
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ActionError {
+    KnockedOut,
+    TargetDown,
+    NotReady,
+    NotEnoughMana {
+        cost: u16,
+        mana: u16,
+    },
+    /// The target is not one the action can be used on
+    WrongTarget,
+    /// Manif actions need a full Manif meter
+    ManifNotCharged,
+    /// The character doesn't have that command or action
+    NotAvailable,
+}
+
+/// Checks whether `chara` is able to use `command` at all right now
+pub fn check_command(chara: &Character, command: Commands) -> Result<(), ActionError> {
+    if command == Commands::Manif && !chara.manif_ready() {
+        Err(ActionError::ManifNotCharged)
+    } else {
+        Ok(())
+    }
+}
+
+/// Checks whether `action` is one `chara` has under `command`
+pub fn check_owned(
+    chara: &Character,
+    command: Commands,
+    action: &Action,
+) -> Result<(), ActionError> {
+    if chara.commands().contains(&command)
+        && chara.act_available[command as usize]
+            .iter()
+            .any(|a| a.id == action.id)
+    {
+        Ok(())
+    } else {
+        Err(ActionError::NotAvailable)
+    }
+}
+
+/// Checks whether `chara` is able to use `action` right now
+pub fn check_action(chara: &Character, action: &Action) -> Result<(), ActionError> {
+    if chara.is_ko() {
+        Err(ActionError::KnockedOut)
+    } else if !chara.is_ready() {
+        Err(ActionError::NotReady)
+    } else if !chara.can_afford(action) {
+        Err(ActionError::NotEnoughMana {
+            cost: action.mana_cost,
+            mana: chara.mana,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+pub struct BattleState {
+    /// Sized within `rules.party_limits` when loaded from an encounter
+    pub enemy_party: Vec<Character>,
+    pub player_party: Vec<Character>,
+    /// Chosen actions, resolved in the same order they were pushed
+    pub queue: VecDeque<QueuedAction>,
+    pub rules: Rules,
+    /// Seconds since the battle started
+    pub elapsed: f32,
+    pub fled: bool,
+    pub log: BattleLog,
+    /// Seed of `rng`, enough to reproduce the whole battle
+    pub seed: u64,
+    /// Every random roll of the battle comes from here
+    pub rng: StdRng,
+    /// Affinities already revealed by elemental hits, by character and element id
+    pub discovered: Vec<(CharaId, String)>,
+    /// What each surviving player character got out of a victory
+    pub gains: Vec<(CharaId, Gains)>,
+}
+impl BattleState {
+    pub fn new(
+        enemy_party: Vec<Character>,
+        player_party: Vec<Character>,
+        rules: Rules,
+        seed: u64,
+    ) -> BattleState {
+        let mut state = BattleState {
+            enemy_party,
+            player_party,
+            queue: VecDeque::new(),
+            rules,
+            elapsed: 0.0,
+            fled: false,
+            log: BattleLog::default(),
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            discovered: vec![],
+            gains: vec![],
+        };
+        state.log_event(BattleEvent::Seed(seed));
+        state
+    }
+
+    pub fn update(&mut self, delta: f32) {
+        self.elapsed += delta;
+        for side in [Side::Player, Side::Enemy] {
+            for index in 0..self.party(side).len() {
+                let id = CharaId { side, index };
+                let was_ko = self.chara(id).is_ko();
+                for change in self.chara_mut(id).update(delta) {
+                    self.log_event(match change {
+                        StatusChange::Tick { kind, amount } => {
+                            BattleEvent::StatusTick { id, kind, amount }
+                        }
+                        StatusChange::Removed(kind) => BattleEvent::StatusRemoved(id, kind),
+                    });
+                }
+                if !was_ko && self.chara(id).is_ko() {
+                    self.log_event(BattleEvent::Ko(id));
+                }
+            }
+        }
+    }
+
+    /// Ends the battle, only if the encounter allows it
+    pub fn flee(&mut self) {
+        self.fled = self.rules.flee_allowed;
+        if !self.fled {
+            self.log_event(BattleEvent::FleeFailed);
+        }
+    }
+
+    pub fn log_event(&mut self, event: BattleEvent) {
+        self.log.push(self.elapsed, event);
+    }
+
+    pub fn party(&self, side: Side) -> &[Character] {
+        match side {
+            Side::Player => &self.player_party,
+            Side::Enemy => &self.enemy_party,
+        }
+    }
+
+    pub fn chara(&self, id: CharaId) -> &Character {
+        &self.party(id.side)[id.index]
+    }
+
+    /// Characters of `side` the action can land on: the knocked out ones for
+    /// revives and the standing ones for everything else
+    fn reachable(&self, side: Side, action: &Action) -> Vec<CharaId> {
+        (0..self.party(side).len())
+            .map(|index| CharaId { side, index })
+            .filter(|id| self.chara(*id).is_ko() == action.revives())
+            .collect()
+    }
+
+    /// Everyone the action may land on when used by `from`, the single
+    /// target kinds choosing one of them and the random ones rolling it
+    pub fn in_reach(&self, from: CharaId, action: &Action) -> Vec<CharaId> {
+        match action.target {
+            TargetKind::SingleEnemy | TargetKind::AllEnemies | TargetKind::RandomEnemy => {
+                self.reachable(from.side.opposite(), action)
+            }
+            TargetKind::SingleAlly | TargetKind::AllAllies => self.reachable(from.side, action),
+            TargetKind::User => vec![from],
+        }
+    }
+
+    /// Who `from` may choose as the target of a single target action,
+    /// empty for the kinds that need no choice
+    pub fn target_candidates(&self, from: CharaId, action: &Action) -> Vec<CharaId> {
+        if action.target.is_single() {
+            self.in_reach(from, action)
+        } else {
+            vec![]
+        }
+    }
+
+    /// Everyone the action lands on, the random kinds rolling their target here
+    fn targets_of(&mut self, act: &QueuedAction) -> Result<Vec<CharaId>, ActionError> {
+        let action = &act.action;
+        let targets = match action.target {
+            TargetKind::SingleEnemy | TargetKind::SingleAlly => {
+                if self.chara(act.to).is_ko() != action.revives() {
+                    return Err(ActionError::TargetDown);
+                }
+                vec![act.to]
+            }
+            TargetKind::RandomEnemy => {
+                let reachable = self.in_reach(act.from, action);
+                reachable
+                    .choose(&mut self.rng)
+                    .into_iter()
+                    .copied()
+                    .collect()
+            }
+            TargetKind::User | TargetKind::AllEnemies | TargetKind::AllAllies => {
+                self.in_reach(act.from, action)
+            }
+        };
+        if targets.is_empty() {
+            return Err(ActionError::TargetDown);
+        }
+        Ok(targets)
+    }
+
+    /// The battle is over once either party is fully knocked out
+    pub fn outcome(&self) -> Option<Outcome> {
+        if self.fled {
+            Some(Outcome::Fled)
+        } else if self.player_party.iter().all(|c| c.is_ko()) {
+            Some(Outcome::Defeat)
+        } else if self.enemy_party.iter().all(|c| c.is_ko()) {
+            Some(Outcome::Victory)
+        } else if self
+            .rules
+            .time_limit
+            .is_some_and(|limit| self.elapsed >= limit)
+        {
+            Some(Outcome::TimeUp)
+        } else {
+            None
+        }
+    }
+
+    /// Gives the experience of every enemy to each standing player character,
+    /// to be called once the battle is won
+    pub fn award_experience(&mut self) {
+        let exp = self
+            .enemy_party
+            .iter()
+            .fold(0u32, |exp, c| exp.saturating_add(c.exp_reward));
+        for index in 0..self.player_party.len() {
+            let id = CharaId {
+                side: Side::Player,
+                index,
+            };
+            if self.chara(id).is_ko() {
+                continue;
+            }
+            let gains = self.chara_mut(id).gain_exp(exp);
+            if gains.leveled_up() {
+                self.log_event(BattleEvent::LevelUp {
+                    id,
+                    level: gains.to_level,
+                });
+            }
+            self.gains.push((id, gains));
+        }
+    }
+
+    pub fn chara_mut(&mut self, id: CharaId) -> &mut Character {
+        self.chara_with_rng(id).0
+    }
+
+    /// Borrows a character along with the battle's rng
+    pub fn chara_with_rng(&mut self, id: CharaId) -> (&mut Character, &mut StdRng) {
+        let party = match id.side {
+            Side::Player => &mut self.player_party,
+            Side::Enemy => &mut self.enemy_party,
+        };
+        (&mut party[id.index], &mut self.rng)
+    }
+
+    /// Checks that the chosen target suits the kind of target of the action
+    fn check_target(&self, act: &QueuedAction) -> Result<(), ActionError> {
+        let right_side = match act.action.target {
+            TargetKind::SingleEnemy => act.to.side != act.from.side,
+            TargetKind::SingleAlly => act.to.side == act.from.side,
+            _ => true,
+        };
+        if right_side && act.to.index < self.party(act.to.side).len() {
+            Ok(())
+        } else {
+            Err(ActionError::WrongTarget)
+        }
+    }
+
+    /// Queues the action and spends its time cost from the acting character
+    pub fn push_action(&mut self, act: QueuedAction) -> Result<(), ActionError> {
+        let chara = self.chara(act.from);
+        let res = check_owned(chara, act.command, &act.action)
+            .and_then(|_| check_action(chara, &act.action))
+            .and_then(|_| check_command(chara, act.command))
+            .and_then(|_| self.check_target(&act));
+        if let Err(err) = res {
+            self.log_event(BattleEvent::Rejected {
+                from: act.from,
+                action: Some(act.action),
+                err,
+            });
+            return Err(err);
+        }
+        self.chara_mut(act.from).spend_time(act.action.time_cost);
+        self.queue.push_back(act);
+        Ok(())
+    }
+
+    /// Resolves every queued action against the current state, oldest first
+    pub fn resolve_queue(&mut self) {
+        while let Some(act) = self.queue.pop_front() {
+            if let Err(err) = self.resolve(&act) {
+                self.log_event(BattleEvent::Rejected {
+                    from: act.from,
+                    action: Some(act.action),
+                    err,
+                });
+            }
+        }
+    }
+
+    /// Remembers that the affinity of `id` to `element` has been seen
+    fn discover(&mut self, id: CharaId, element: &str) {
+        if !self.is_discovered(id, element) {
+            self.discovered.push((id, element.to_string()));
+        }
+    }
+
+    pub fn is_discovered(&self, id: CharaId, element: &str) -> bool {
+        self.discovered
+            .iter()
+            .any(|(i, e)| *i == id && e == element)
+    }
+
+    fn charge_manif(&mut self, id: CharaId, amount: u16, rate: f32) {
+        if self.chara_mut(id).charge_manif(amount, rate) {
+            self.log_event(BattleEvent::ManifReady(id));
+        }
+    }
+
+    fn inflict(&mut self, id: CharaId, spec: StatusSpec, duration: f32) {
+        match self.chara_mut(id).apply_status(spec, duration) {
+            Some(cancelled) => self.log_event(BattleEvent::StatusRemoved(id, cancelled)),
+            None => self.log_event(BattleEvent::StatusApplied(id, spec.kind)),
+        }
+    }
+
+    /// Resolves the action on each of its targets, logging what it did to every one
+    fn resolve(&mut self, act: &QueuedAction) -> Result<(), ActionError> {
+        if self.chara(act.from).is_ko() {
+            return Err(ActionError::KnockedOut);
+        }
+        let targets = self.targets_of(act)?;
+
+        // Mana and Manif charge are only spent once the action actually happens
+        let chara = self.chara_mut(act.from);
+        if !chara.can_afford(&act.action) {
+            return Err(ActionError::NotEnoughMana {
+                cost: act.action.mana_cost,
+                mana: chara.mana,
+            });
+        }
+        check_command(chara, act.command)?;
+        chara.spend_mana(act.action.mana_cost);
+        if act.command == Commands::Manif {
+            chara.manif = 0.0;
+        }
+
+        for to in targets {
+            let was_ko = self.chara(to).is_ko();
+            let result = self.resolve_on(act, to);
+            self.log_event(BattleEvent::Action {
+                from: act.from,
+                to,
+                action: act.action.clone(),
+                result,
+            });
+            if result.is_some_and(|r| r.miss) {
+                continue;
+            }
+            if let Some(result) = result {
+                self.charge_manif(to, result.dealt(), MANIF_TAKEN_RATE);
+                // Manif damage doesn't charge the meter it just emptied
+                if act.command != Commands::Manif {
+                    self.charge_manif(act.from, result.dealt(), MANIF_DEALT_RATE);
+                }
+                if let (Some(_), Some(element)) = (result.affinity, &act.action.element) {
+                    self.discover(to, element);
+                }
+            }
+
+            for effect in &act.action.effects {
+                if let Some(outcome) = self.chara_mut(to).apply_effect(*effect) {
+                    self.log_event(BattleEvent::Effect { id: to, outcome });
+                }
+            }
+            if !was_ko && self.chara(to).is_ko() {
+                self.log_event(BattleEvent::Ko(to));
+            } else if let Some(spec) = act.action.status {
+                self.inflict(to, spec, act.action.duration);
+            }
+        }
+        Ok(())
+    }
+
+    fn resolve_on(&mut self, act: &QueuedAction, to: CharaId) -> Option<DamageResult> {
+        match act.command {
+            // Actions that only inflict a status or have effects don't deal damage
+            Commands::Attack | Commands::Magic | Commands::Ability | Commands::Manif
+                if act.action.damage == 0
+                    && (act.action.status.is_some() || !act.action.effects.is_empty()) =>
+            {
+                None
+            }
+            Commands::Attack | Commands::Magic | Commands::Ability | Commands::Manif => {
+                let stats = self.chara(act.from).effective_stats();
+                let (target, rng) = self.chara_with_rng(to);
+                Some(resolve_damage(rng, &stats, target, &act.action))
+            }
+            Commands::Defend => {
+                self.chara_mut(to).defend(&act.action);
+                None
+            }
+            Commands::Max => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAYER: CharaId = CharaId {
+        side: Side::Player,
+        index: 0,
+    };
+    const ENEMY: CharaId = CharaId {
+        side: Side::Enemy,
+        index: 0,
+    };
+
+    /// A battle of one against one, the player being ready to act
+    fn battle() -> BattleState {
+        let player = Character {
+            time: MAX_TIME,
+            ..Default::default()
+        };
+        BattleState::new(
+            vec![Character::default()],
+            vec![player],
+            Rules::default(),
+            1,
+        )
+    }
+
+    fn attack(action: Action, to: CharaId) -> QueuedAction {
+        QueuedAction {
+            from: PLAYER,
+            command: Commands::Attack,
+            action,
+            to,
+        }
+    }
+
+    #[test]
+    fn queues_a_valid_action() {
+        let mut state = battle();
+        assert_eq!(state.push_action(attack(Action::default(), ENEMY)), Ok(()));
+        assert_eq!(state.queue.len(), 1);
+        assert_eq!(state.chara(PLAYER).time, 0.0);
+    }
+
+    #[test]
+    fn rejects_characters_not_ready() {
+        let mut state = battle();
+        state.chara_mut(PLAYER).time = 0.0;
+        let res = state.push_action(attack(Action::default(), ENEMY));
+        assert_eq!(res, Err(ActionError::NotReady));
+        assert!(state.queue.is_empty());
+    }
+
+    #[test]
+    fn logs_every_rejection() {
+        let mut state = battle();
+        state.chara_mut(PLAYER).time = 0.0;
+        let _ = state.push_action(attack(Action::default(), ENEMY));
+        let rejected = state
+            .log
+            .iter()
+            .filter(|entry| matches!(entry.event, BattleEvent::Rejected { .. }))
+            .count();
+        assert_eq!(rejected, 1);
+    }
+
+    #[test]
+    fn rejects_actions_it_cant_afford() {
+        let mut state = battle();
+        state.chara_mut(PLAYER).mana = 5;
+        let action = Action {
+            mana_cost: 10,
+            ..Default::default()
+        };
+        let res = state.push_action(attack(action.clone(), ENEMY));
+        assert_eq!(res, Err(ActionError::NotEnoughMana { cost: 10, mana: 5 }));
+
+        state.chara_mut(PLAYER).mana = 10;
+        assert_eq!(state.push_action(attack(action, ENEMY)), Ok(()));
+        state.resolve_queue();
+        assert_eq!(state.chara(PLAYER).mana, 0);
+    }
+
+    /// Two against two, the second of each party being knocked out
+    fn battle_with_kos() -> BattleState {
+        let ko = Character {
+            health: 0,
+            ..Default::default()
+        };
+        let mut state = battle();
+        state.player_party.push(ko.clone());
+        state.enemy_party.push(ko);
+        state
+    }
+
+    fn targeting(target: TargetKind) -> Action {
+        Action {
+            target,
+            ..Default::default()
+        }
+    }
+
+    fn revive() -> Action {
+        Action {
+            target: TargetKind::SingleAlly,
+            effects: vec![Effect::Revive(10)],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn rejects_targets_on_the_wrong_side() {
+        let mut state = battle();
+        let res = state.push_action(attack(Action::default(), PLAYER));
+        assert_eq!(res, Err(ActionError::WrongTarget));
+        let heal = targeting(TargetKind::SingleAlly);
+        assert_eq!(
+            state.push_action(attack(heal, ENEMY)),
+            Err(ActionError::WrongTarget)
+        );
+    }
+
+    #[test]
+    fn candidates_are_standing_unless_reviving() {
+        let state = battle_with_kos();
+        let downed = CharaId {
+            side: Side::Player,
+            index: 1,
+        };
+        let enemies = state.target_candidates(PLAYER, &targeting(TargetKind::SingleEnemy));
+        assert_eq!(enemies, vec![ENEMY]);
+        let allies = state.target_candidates(PLAYER, &targeting(TargetKind::SingleAlly));
+        assert_eq!(allies, vec![PLAYER]);
+        assert_eq!(state.target_candidates(PLAYER, &revive()), vec![downed]);
+        // Nothing to choose for the rest of the kinds
+        let all = targeting(TargetKind::AllEnemies);
+        assert!(state.target_candidates(PLAYER, &all).is_empty());
+        // Though they still reach whoever they may land on
+        assert_eq!(state.in_reach(PLAYER, &all), vec![ENEMY]);
+        let user = targeting(TargetKind::User);
+        assert_eq!(state.in_reach(PLAYER, &user), vec![PLAYER]);
+    }
+
+    #[test]
+    fn resolves_who_each_kind_lands_on() {
+        let mut state = battle_with_kos();
+        let mut targets =
+            |target: TargetKind, to: CharaId| state.targets_of(&attack(targeting(target), to));
+        assert_eq!(targets(TargetKind::AllEnemies, PLAYER), Ok(vec![ENEMY]));
+        assert_eq!(targets(TargetKind::AllAllies, PLAYER), Ok(vec![PLAYER]));
+        assert_eq!(targets(TargetKind::User, ENEMY), Ok(vec![PLAYER]));
+        assert_eq!(targets(TargetKind::RandomEnemy, PLAYER), Ok(vec![ENEMY]));
+        let downed_enemy = CharaId {
+            side: Side::Enemy,
+            index: 1,
+        };
+        assert_eq!(
+            targets(TargetKind::SingleEnemy, downed_enemy),
+            Err(ActionError::TargetDown)
+        );
+    }
+
+    #[test]
+    fn revives_only_land_on_knocked_out_allies() {
+        let mut state = battle_with_kos();
+        let downed = CharaId {
+            side: Side::Player,
+            index: 1,
+        };
+        assert_eq!(
+            state.targets_of(&attack(revive(), downed)),
+            Ok(vec![downed])
+        );
+        assert_eq!(
+            state.targets_of(&attack(revive(), PLAYER)),
+            Err(ActionError::TargetDown)
+        );
+    }
+
+    #[test]
+    fn rejects_manif_before_its_meter_is_full() {
+        let mut state = battle();
+        let mut act = attack(Action::default(), ENEMY);
+        act.command = Commands::Manif;
+        assert_eq!(
+            state.push_action(act.clone()),
+            Err(ActionError::ManifNotCharged)
+        );
+
+        state.chara_mut(PLAYER).manif = MANIF_CHARGE;
+        assert_eq!(state.push_action(act), Ok(()));
+        state.resolve_queue();
+        assert_eq!(state.chara(PLAYER).manif, 0.0);
+    }
+
+    #[test]
+    fn damage_charges_the_manif_meter() {
+        let mut state = battle();
+        let action = Action {
+            damage: 10,
+            accuracy: 2.0,
+            ..Default::default()
+        };
+        state.push_action(attack(action, ENEMY)).unwrap();
+        state.resolve_queue();
+        assert!(state.chara(PLAYER).manif > 0.0);
+        assert!(state.chara(ENEMY).manif > state.chara(PLAYER).manif);
+    }
+
+    #[test]
+    fn elemental_hits_reveal_affinities() {
+        let mut state = battle();
+        state
+            .chara_mut(ENEMY)
+            .affinities
+            .insert("fire".to_string(), Affinity::Weak);
+        let fire = |element: &str| Action {
+            element: Some(element.to_string()),
+            accuracy: 2.0,
+            damage: 1,
+            ..Default::default()
+        };
+
+        state.push_action(attack(fire("ice"), ENEMY)).unwrap();
+        state.resolve_queue();
+        assert!(!state.is_discovered(ENEMY, "ice"));
+
+        state.chara_mut(PLAYER).time = MAX_TIME;
+        state.push_action(attack(fire("fire"), ENEMY)).unwrap();
+        state.resolve_queue();
+        assert!(state.is_discovered(ENEMY, "fire"));
+        assert!(!state.is_discovered(PLAYER, "fire"));
+    }
+
+    #[test]
+    fn rejects_actions_it_doesnt_have() {
+        let mut state = battle();
+        let magic = QueuedAction {
+            command: Commands::Magic,
+            ..attack(Action::default(), ENEMY)
+        };
+        assert_eq!(state.push_action(magic), Err(ActionError::NotAvailable));
+        let unknown = Action {
+            id: "unknown".to_string(),
+            ..Default::default()
+        };
+        let res = state.push_action(attack(unknown, ENEMY));
+        assert_eq!(res, Err(ActionError::NotAvailable));
+        assert!(state.queue.is_empty());
+    }
+}
@@ -0,0 +1,226 @@
+use crate::{battle::*, characters::*};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Playback speeds, as multipliers of real time
+pub const SPEEDS: [f32; 6] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+/// Most steps `Playback::next_event` takes looking for something to happen
+const MAX_SEEK_STEPS: u32 = 10_000;
+
+/// Something the player did to the engine
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Input {
+    Submit(QueuedAction),
+    Flee,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedInput {
+    /// Engine steps taken before the input was given
+    pub tick: u64,
+    pub input: Input,
+}
+
+/// Everything needed to play a battle again exactly as it happened
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Replay {
+    pub title: String,
+    /// Name of the enemy controller
+    pub ai: String,
+    pub seed: u64,
+    pub rules: Rules,
+    pub player_party: Vec<Character>,
+    pub enemy_party: Vec<Character>,
+    pub inputs: Vec<RecordedInput>,
+}
+
+/// Feeds the inputs of a replay back into an engine at the steps they were given
+pub struct Playback {
+    inputs: VecDeque<RecordedInput>,
+    pub paused: bool,
+    /// Index into `SPEEDS`
+    speed: usize,
+    /// Real time scaled by the playback speed, waiting to add up to a `STEP`
+    pending: f32,
+}
+impl Playback {
+    pub fn new(inputs: Vec<RecordedInput>) -> Playback {
+        Playback {
+            inputs: inputs.into(),
+            paused: false,
+            speed: 2,
+            pending: 0.0,
+        }
+    }
+
+    pub fn speed(&self) -> f32 {
+        SPEEDS[self.speed]
+    }
+
+    pub fn faster(&mut self) {
+        self.speed = (self.speed + 1).min(SPEEDS.len() - 1);
+    }
+
+    pub fn slower(&mut self) {
+        self.speed = self.speed.saturating_sub(1);
+    }
+
+    /// Plays `delta` seconds of the battle, scaled by the speed. Does nothing while paused
+    pub fn advance(&mut self, engine: &mut Engine, delta: f32) {
+        if self.paused {
+            return;
+        }
+        self.pending += delta * self.speed();
+        while self.pending >= STEP {
+            self.pending -= STEP;
+            self.step(engine);
+        }
+    }
+
+    /// Plays a single engine step, giving first every input due by then
+    pub fn step(&mut self, engine: &mut Engine) {
+        while self
+            .inputs
+            .front()
+            .is_some_and(|rec| rec.tick <= engine.tick())
+        {
+            if let Some(rec) = self.inputs.pop_front() {
+                engine.apply(rec.input);
+            }
+        }
+        engine.step();
+    }
+
+    /// Plays until something new shows up in the battle log or the battle ends
+    pub fn next_event(&mut self, engine: &mut Engine) {
+        let total = engine.state().log.total();
+        for _ in 0..MAX_SEEK_STEPS {
+            if engine.outcome().is_some() || engine.state().log.total() != total {
+                return;
+            }
+            self.step(engine);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Steps after which a battle that hasn't ended fails the test
+    const MAX_TICKS: u64 = 20_000;
+
+    fn fighter(name: &str) -> Character {
+        let mut chara = Character {
+            name: name.to_string(),
+            ..Default::default()
+        };
+        let attack = &mut chara.act_available[Commands::Attack as usize][0];
+        attack.id = "strike".to_string();
+        attack.damage = 15;
+        chara
+    }
+
+    /// Plays the player party by hand, attacking the first standing enemy
+    fn play(engine: &mut Engine) {
+        while engine.outcome().is_none() && engine.tick() < MAX_TICKS {
+            let state = engine.state();
+            let target = state.enemy_party.iter().position(|c| !c.is_ko());
+            let ready = (0..state.player_party.len())
+                .filter(|&index| state.player_party[index].is_ready())
+                .collect::<Vec<usize>>();
+            for (index, to) in ready.into_iter().zip(target.into_iter().cycle()) {
+                let from = CharaId {
+                    side: Side::Player,
+                    index,
+                };
+                let action =
+                    engine.state().chara(from).act_available[Commands::Attack as usize][0].clone();
+                let _ = engine.submit(QueuedAction {
+                    from,
+                    command: Commands::Attack,
+                    action,
+                    to: CharaId {
+                        side: Side::Enemy,
+                        index: to,
+                    },
+                });
+            }
+            engine.step();
+        }
+    }
+
+    #[test]
+    fn plays_a_battle_back_exactly() {
+        let state = BattleState::new(
+            vec![fighter("Enemy"), fighter("Enemy2")],
+            vec![fighter("Player"), fighter("Player2")],
+            Rules::default(),
+            42,
+        );
+        let mut engine = Engine::new(state, Box::new(RandomController));
+        play(&mut engine);
+        assert!(engine.outcome().is_some());
+        assert!(!engine.replay().inputs.is_empty());
+
+        // Through json, as it would be saved and loaded
+        let json = serde_json::to_string(engine.replay()).unwrap();
+        let replay: Replay = serde_json::from_str(&json).unwrap();
+        let mut replayed = Engine::from_replay(&replay).unwrap();
+        let mut playback = Playback::new(replay.inputs);
+        while replayed.outcome().is_none() && replayed.tick() < MAX_TICKS {
+            playback.step(&mut replayed);
+        }
+
+        let (a, b) = (engine.state(), replayed.state());
+        assert_eq!(replayed.outcome(), engine.outcome());
+        assert_eq!(replayed.tick(), engine.tick());
+        assert_eq!(a.log.total(), b.log.total());
+        for (x, y) in a
+            .player_party
+            .iter()
+            .chain(&a.enemy_party)
+            .zip(b.player_party.iter().chain(&b.enemy_party))
+        {
+            assert_eq!((x.health, x.mana, x.time), (y.health, y.mana, y.time));
+        }
+    }
+
+    #[test]
+    fn rejects_edited_actions_on_playback() {
+        let state = BattleState::new(
+            vec![fighter("Enemy")],
+            vec![fighter("Player")],
+            Rules::default(),
+            7,
+        );
+        let mut engine = Engine::new(state, Box::new(RandomController));
+        play(&mut engine);
+        let mut replay = engine.replay().clone();
+        let edited = replay
+            .inputs
+            .iter_mut()
+            .find_map(|recorded| match &mut recorded.input {
+                Input::Submit(act) => {
+                    act.action.id = "stolen".to_string();
+                    act.action.damage = u16::MAX;
+                    Some(recorded.tick)
+                }
+                Input::Flee => None,
+            })
+            .unwrap();
+
+        let mut replayed = Engine::from_replay(&replay).unwrap();
+        let mut playback = Playback::new(replay.inputs);
+        while replayed.tick() <= edited {
+            playback.step(&mut replayed);
+        }
+        assert!(replayed.state().log.iter().any(|entry| matches!(
+            entry.event,
+            BattleEvent::Rejected {
+                err: ActionError::NotAvailable,
+                ..
+            }
+        )));
+    }
+}
@@ -0,0 +1,98 @@
+//! Runs many AI-vs-AI battles of an encounter and reports how they went
+mod report;
+
+use battle_rpg::{battle::*, file_io::*};
+use report::Report;
+use std::{env, fs, process, str::FromStr};
+
+/// Seconds a battle may last when the encounter has no time limit of its own,
+/// battles reaching it counting as draws
+const DEFAULT_MAX_TIME: f32 = 600.0;
+
+/// What to simulate, how many times and where to write the report
+pub struct Args {
+    /// Id or path of the encounter to simulate
+    pub encounter: String,
+    pub battles: u32,
+    /// Seed of the first battle, the next ones counting up from it
+    pub seed: u64,
+    pub enemy_ai: String,
+    pub player_ai: String,
+    pub max_time: f32,
+    pub csv: Option<String>,
+    pub json: Option<String>,
+}
+impl Args {
+    pub fn parse() -> Args {
+        let mut args = Args {
+            encounter: "default".to_string(),
+            battles: 100,
+            seed: 0,
+            enemy_ai: "heuristic".to_string(),
+            player_ai: "heuristic".to_string(),
+            max_time: DEFAULT_MAX_TIME,
+            csv: None,
+            json: None,
+        };
+        let mut iter = env::args().skip(1);
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "-n" | "--battles" => args.battles = parse_value(&arg, iter.next()),
+                "--seed" => args.seed = parse_value(&arg, iter.next()),
+                "--ai" => args.enemy_ai = parse_value(&arg, iter.next()),
+                "--player-ai" => args.player_ai = parse_value(&arg, iter.next()),
+                "--max-time" => args.max_time = parse_value(&arg, iter.next()),
+                "--csv" => args.csv = Some(parse_value(&arg, iter.next())),
+                "--json" => args.json = Some(parse_value(&arg, iter.next())),
+                _ => args.encounter = arg,
+            }
+        }
+        args
+    }
+}
+
+fn parse_value<T: FromStr>(flag: &str, value: Option<String>) -> T {
+    match value.map(|v| v.parse()) {
+        Some(Ok(value)) => value,
+        _ => exit_with(format!("{} expects a valid value", flag)),
+    }
+}
+
+fn exit_with(msg: impl ToString) -> ! {
+    eprintln!("{}", msg.to_string());
+    process::exit(1);
+}
+
+fn controller(name: &str) -> Box<dyn EnemyController> {
+    enemy_controller(name).unwrap_or_else(|| exit_with(format!("unknown ai `{}`", name)))
+}
+
+fn main() {
+    let args = Args::parse();
+    let encounter = load_encounter(&args.encounter).unwrap_or_else(|err| exit_with(err));
+    let max_time = encounter.rules.time_limit.unwrap_or(args.max_time);
+
+    let mut report = Report::new(&encounter, args.seed);
+    for i in 0..args.battles {
+        let seed = args.seed.wrapping_add(i as u64);
+        let mut engine =
+            Engine::from_encounter(encounter.clone(), controller(&args.enemy_ai), seed);
+        engine.set_player_ai(controller(&args.player_ai));
+
+        while engine.outcome().is_none() && engine.state().elapsed < max_time {
+            engine.step();
+            report.add_events(&engine.drain_events());
+        }
+        report.end_battle(engine.outcome(), engine.state().elapsed);
+    }
+
+    let summary = report.summary();
+    print!("{}", summary.table());
+    if let Some(path) = &args.csv {
+        fs::write(path, summary.csv()).unwrap_or_else(|err| exit_with(err));
+    }
+    if let Some(path) = &args.json {
+        let json = serde_json::to_string_pretty(&summary).unwrap_or_else(|err| exit_with(err));
+        fs::write(path, json).unwrap_or_else(|err| exit_with(err));
+    }
+}
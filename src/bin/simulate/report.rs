@@ -0,0 +1,258 @@
+use battle_rpg::{
+    battle::{BattleEvent, CharaId, LogEntry, Outcome, Side},
+    file_io::Encounter,
+};
+use serde::Serialize;
+use std::{collections::BTreeMap, fmt::Write};
+
+#[derive(Default)]
+struct CharaTotals {
+    dealt: u64,
+    taken: u64,
+    kos: u32,
+}
+
+#[derive(Default)]
+struct ActionTotals {
+    uses: u32,
+    damage: u64,
+}
+
+/// Everything gathered while running the battles
+pub struct Report {
+    title: String,
+    first_seed: u64,
+    battles: u32,
+    outcomes: BTreeMap<&'static str, u32>,
+    total_length: f32,
+    names: Vec<(CharaId, String)>,
+    charas: Vec<CharaTotals>,
+    actions: BTreeMap<String, ActionTotals>,
+    /// Who used what and when, as an action hitting several targets is
+    /// logged once per target but only counts as one use
+    last_use: Option<(CharaId, String, f32)>,
+}
+
+#[derive(Serialize)]
+pub struct CharaSummary {
+    pub side: Side,
+    pub name: String,
+    pub damage_per_battle: f32,
+    pub taken_per_battle: f32,
+    pub ko_rate: f32,
+}
+
+#[derive(Serialize)]
+pub struct ActionSummary {
+    pub id: String,
+    pub uses: u32,
+    pub uses_per_battle: f32,
+    pub damage_per_use: f32,
+}
+
+/// Averages over every battle, as written to the json file
+#[derive(Serialize)]
+pub struct Summary {
+    pub encounter: String,
+    pub battles: u32,
+    pub first_seed: u64,
+    /// Share of battles ending in each way, draws being battles that hit the time cap
+    pub outcomes: BTreeMap<&'static str, f32>,
+    /// Seconds
+    pub avg_length: f32,
+    pub characters: Vec<CharaSummary>,
+    pub actions: Vec<ActionSummary>,
+}
+
+fn outcome_name(outcome: Option<Outcome>) -> &'static str {
+    match outcome {
+        Some(Outcome::Victory) => "victory",
+        Some(Outcome::Defeat) => "defeat",
+        Some(Outcome::Fled) => "fled",
+        Some(Outcome::TimeUp) => "time_up",
+        None => "draw",
+    }
+}
+
+impl Report {
+    pub fn new(encounter: &Encounter, first_seed: u64) -> Report {
+        let names = [
+            (Side::Player, &encounter.player_party),
+            (Side::Enemy, &encounter.enemy_party),
+        ]
+        .into_iter()
+        .flat_map(|(side, party)| {
+            party
+                .iter()
+                .enumerate()
+                .map(move |(index, c)| (CharaId { side, index }, c.name.clone()))
+        })
+        .collect::<Vec<_>>();
+        Report {
+            title: encounter.title.clone(),
+            first_seed,
+            battles: 0,
+            outcomes: BTreeMap::new(),
+            total_length: 0.0,
+            charas: names.iter().map(|_| CharaTotals::default()).collect(),
+            names,
+            actions: BTreeMap::new(),
+            last_use: None,
+        }
+    }
+
+    /// Players come first in `names`, followed by the enemies
+    fn chara(&mut self, id: CharaId) -> &mut CharaTotals {
+        let players = self
+            .names
+            .iter()
+            .filter(|(n, _)| n.side == Side::Player)
+            .count();
+        match id.side {
+            Side::Player => &mut self.charas[id.index],
+            Side::Enemy => &mut self.charas[players + id.index],
+        }
+    }
+
+    pub fn add_events(&mut self, events: &[LogEntry]) {
+        for entry in events {
+            match &entry.event {
+                BattleEvent::Action {
+                    from,
+                    to,
+                    action,
+                    result,
+                } => {
+                    let amount = result.map_or(0, |r| r.dealt()) as u64;
+                    self.chara(*from).dealt += amount;
+                    self.chara(*to).taken += amount;
+                    let key = if action.id.is_empty() {
+                        action.name.clone()
+                    } else {
+                        action.id.clone()
+                    };
+                    let used = Some((*from, key.clone(), entry.time));
+                    let totals = self.actions.entry(key).or_default();
+                    if self.last_use != used {
+                        totals.uses += 1;
+                    }
+                    totals.damage += amount;
+                    self.last_use = used;
+                }
+                BattleEvent::Ko(id) => self.chara(*id).kos += 1,
+                _ => {}
+            }
+        }
+    }
+
+    pub fn end_battle(&mut self, outcome: Option<Outcome>, length: f32) {
+        self.last_use = None;
+        self.battles += 1;
+        *self.outcomes.entry(outcome_name(outcome)).or_default() += 1;
+        self.total_length += length;
+    }
+
+    pub fn summary(&self) -> Summary {
+        let battles = self.battles.max(1) as f32;
+        Summary {
+            encounter: self.title.clone(),
+            battles: self.battles,
+            first_seed: self.first_seed,
+            outcomes: self
+                .outcomes
+                .iter()
+                .map(|(name, count)| (*name, *count as f32 / battles))
+                .collect(),
+            avg_length: self.total_length / battles,
+            characters: self
+                .names
+                .iter()
+                .zip(self.charas.iter())
+                .map(|((id, name), totals)| CharaSummary {
+                    side: id.side,
+                    name: name.clone(),
+                    damage_per_battle: totals.dealt as f32 / battles,
+                    taken_per_battle: totals.taken as f32 / battles,
+                    ko_rate: totals.kos as f32 / battles,
+                })
+                .collect(),
+            actions: self
+                .actions
+                .iter()
+                .map(|(id, totals)| ActionSummary {
+                    id: id.clone(),
+                    uses: totals.uses,
+                    uses_per_battle: totals.uses as f32 / battles,
+                    damage_per_use: totals.damage as f32 / totals.uses.max(1) as f32,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Summary {
+    /// Human readable tables
+    pub fn table(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "{} - {} battles from seed {}",
+            self.encounter, self.battles, self.first_seed
+        );
+        for (name, rate) in &self.outcomes {
+            let _ = writeln!(out, "  {:<10} {:>6.1}%", name, rate * 100.0);
+        }
+        let _ = writeln!(out, "  {:<10} {:>6.1}s", "length", self.avg_length);
+
+        let _ = writeln!(
+            out,
+            "\n{:<8} {:<16} {:>10} {:>10} {:>8}",
+            "side", "character", "dealt", "taken", "ko"
+        );
+        for c in &self.characters {
+            let _ = writeln!(
+                out,
+                "{:<8} {:<16} {:>10.1} {:>10.1} {:>7.1}%",
+                format!("{:?}", c.side),
+                c.name,
+                c.damage_per_battle,
+                c.taken_per_battle,
+                c.ko_rate * 100.0
+            );
+        }
+
+        let _ = writeln!(
+            out,
+            "\n{:<25} {:>8} {:>10} {:>10}",
+            "action", "uses", "per battle", "damage"
+        );
+        for a in &self.actions {
+            let _ = writeln!(
+                out,
+                "{:<25} {:>8} {:>10.2} {:>10.1}",
+                a.id, a.uses, a.uses_per_battle, a.damage_per_use
+            );
+        }
+        out
+    }
+
+    /// Every figure as a `metric,subject,value` row, easy to pivot in a spreadsheet
+    pub fn csv(&self) -> String {
+        let mut out = String::from("metric,subject,value\n");
+        for (name, rate) in &self.outcomes {
+            let _ = writeln!(out, "outcome_rate,{},{}", name, rate);
+        }
+        let _ = writeln!(out, "avg_length,,{}", self.avg_length);
+        for c in &self.characters {
+            let subject = format!("{:?}/{}", c.side, c.name);
+            let _ = writeln!(out, "damage_per_battle,{},{}", subject, c.damage_per_battle);
+            let _ = writeln!(out, "taken_per_battle,{},{}", subject, c.taken_per_battle);
+            let _ = writeln!(out, "ko_rate,{},{}", subject, c.ko_rate);
+        }
+        for a in &self.actions {
+            let _ = writeln!(out, "action_uses,{},{}", a.id, a.uses);
+            let _ = writeln!(out, "action_damage_per_use,{},{}", a.id, a.damage_per_use);
+        }
+        out
+    }
+}
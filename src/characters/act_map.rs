@@ -0,0 +1,40 @@
+//! `act_available` is written in files as a map from each command to its
+//! actions, where every action is either a skill id or the whole action
+
+use super::{Action, Commands};
+use serde::{de::Error, ser::SerializeMap, Deserialize, Deserializer, Serializer};
+use serde_json::Value;
+use std::collections::HashMap;
+
+type Actions = [Vec<Action>; Commands::Max as usize];
+
+pub fn serialize<S: Serializer>(acts: &Actions, s: S) -> Result<S::Ok, S::Error> {
+    let mut map = s.serialize_map(None)?;
+    for (cmd, list) in Commands::ALL.iter().zip(acts.iter()) {
+        if !list.is_empty() {
+            map.serialize_entry(cmd, list)?;
+        }
+    }
+    map.end()
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Actions, D::Error> {
+    let map = HashMap::<Commands, Vec<Value>>::deserialize(d)?;
+    let mut acts = Actions::default();
+    for (cmd, list) in map {
+        if cmd == Commands::Max {
+            return Err(D::Error::custom("`Max` is not a real command"));
+        }
+        // Whole actions are parsed on their own so their errors name the field
+        acts[cmd as usize] = list
+            .into_iter()
+            .enumerate()
+            .map(|(i, act)| match act {
+                Value::String(id) => Ok(Action::reference(id)),
+                act => Action::deserialize(act)
+                    .map_err(|err| D::Error::custom(format!("`{}` action {}: {}", cmd, i, err))),
+            })
+            .collect::<Result<_, _>>()?;
+    }
+    Ok(acts)
+}
@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+
+use super::StatusKind;
+
+/// What an action does to each of its targets besides dealing its `damage`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Effect {
+    /// Recovers health, up to `max_health`
+    Heal(u16),
+    /// Recovers mana, up to `max_mana`
+    RestoreMana(u16),
+    /// Brings a knocked out target back with the given health
+    Revive(u16),
+    /// Removes every harmful status
+    Cleanse,
+    /// Fills the time gauge by the given seconds, up to `max_time`
+    GrantTime(f32),
+}
+
+/// What an effect actually did to its target
+#[derive(Debug, Clone, PartialEq)]
+pub enum EffectOutcome {
+    Healed(u16),
+    ManaRestored(u16),
+    Revived(u16),
+    Cleansed(Vec<StatusKind>),
+    TimeGranted(f32),
+}
@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+
+/// Damage multiplier against a weak character
+pub const WEAK_MULTIPLIER: f32 = 1.5;
+/// Damage multiplier against a resistant character
+pub const RESIST_MULTIPLIER: f32 = 0.5;
+
+/// An element actions can have, as defined in the element file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Element {
+    pub id: String,
+    pub name: String,
+}
+
+/// How a character takes the damage of an element
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Affinity {
+    Weak,
+    Resist,
+    Immune,
+    /// Heals from the damage instead of losing health
+    Absorb,
+}
+impl Affinity {
+    /// Applies the affinity to the damage, absorbed damage staying as it is
+    pub fn scale(self, dmg: u16) -> u16 {
+        let factor = match self {
+            Affinity::Weak => WEAK_MULTIPLIER,
+            Affinity::Resist => RESIST_MULTIPLIER,
+            Affinity::Immune => 0.0,
+            Affinity::Absorb => 1.0,
+        };
+        (dmg as f32 * factor).round().min(u16::MAX as f32) as u16
+    }
+}
@@ -0,0 +1,177 @@
+use serde::{Deserialize, Serialize};
+
+use super::{act_map, Action, Character, Commands, Stats};
+
+/// What reaching a level takes and gives, the first entry of a character's
+/// `growth` being level 2
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LevelUp {
+    /// Total experience needed to reach the level
+    pub exp: u32,
+    /// Added to the character's stats
+    #[serde(default)]
+    pub stats: Stats,
+    /// Added to both `max_health` and `health`
+    #[serde(default)]
+    pub health: u16,
+    /// Added to both `max_mana` and `mana`
+    #[serde(default)]
+    pub mana: u16,
+    /// Actions learnt on reaching the level, by command
+    #[serde(default, with = "act_map")]
+    pub unlocks: [Vec<Action>; Commands::Max as usize],
+}
+
+/// Everything a character got out of a battle
+#[derive(Debug, Clone, Default)]
+pub struct Gains {
+    pub exp: u32,
+    pub from_level: u16,
+    pub to_level: u16,
+    pub stats: Stats,
+    pub health: u16,
+    pub mana: u16,
+    /// Actions learnt along with the command they belong to
+    pub learnt: Vec<(Commands, Action)>,
+}
+impl Gains {
+    pub fn leveled_up(&self) -> bool {
+        self.to_level > self.from_level
+    }
+}
+
+impl Character {
+    /// Next level up, if there's any left
+    pub fn next_level(&self) -> Option<&LevelUp> {
+        self.growth.get(self.level.max(1) as usize - 1)
+    }
+
+    /// Adds `exp` experience and goes through every level it reaches
+    pub fn gain_exp(&mut self, exp: u32) -> Gains {
+        self.exp = self.exp.saturating_add(exp);
+        let mut gains = Gains {
+            exp,
+            from_level: self.level,
+            to_level: self.level,
+            ..Default::default()
+        };
+        while let Some(next) = self.next_level().filter(|l| self.exp >= l.exp).cloned() {
+            self.level = self.level.max(1).saturating_add(1);
+            // Growth comes from data files, so it can't be trusted not to overflow
+            self.stats.attack = self.stats.attack.saturating_add(next.stats.attack);
+            self.stats.defense = self.stats.defense.saturating_add(next.stats.defense);
+            self.stats.hope = self.stats.hope.saturating_add(next.stats.hope);
+            self.max_health = self.max_health.saturating_add(next.health);
+            self.health = self.health.saturating_add(next.health);
+            self.max_mana = self.max_mana.saturating_add(next.mana);
+            self.mana = self.mana.saturating_add(next.mana);
+
+            gains.to_level = self.level;
+            gains.stats.attack = gains.stats.attack.saturating_add(next.stats.attack);
+            gains.stats.defense = gains.stats.defense.saturating_add(next.stats.defense);
+            gains.stats.hope = gains.stats.hope.saturating_add(next.stats.hope);
+            gains.health = gains.health.saturating_add(next.health);
+            gains.mana = gains.mana.saturating_add(next.mana);
+            for (cmd, acts) in Commands::ALL.iter().zip(next.unlocks) {
+                for act in acts {
+                    if self.learn(*cmd, act.clone()) {
+                        gains.learnt.push((*cmd, act));
+                    }
+                }
+            }
+        }
+        gains
+    }
+
+    /// Adds the action to `cmd`, unlocking the command if needed, which is the
+    /// only way commands get unlocked. Returns whether the action is new to the
+    /// character
+    pub fn learn(&mut self, cmd: Commands, act: Action) -> bool {
+        if !self.cmd_available.contains(&cmd) {
+            self.cmd_available.push(cmd);
+            self.cmd_available.sort_unstable_by_key(|c| *c as usize);
+        }
+        let acts = &mut self.act_available[cmd as usize];
+        if acts.iter().any(|a| a.id == act.id && a.name == act.name) {
+            return false;
+        }
+        acts.push(act);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level(exp: u32, attack: u16, health: u16) -> LevelUp {
+        LevelUp {
+            exp,
+            stats: Stats {
+                attack,
+                ..Default::default()
+            },
+            health,
+            ..Default::default()
+        }
+    }
+
+    fn chara() -> Character {
+        let mut third = level(100, 2, 10);
+        third.unlocks[Commands::Magic as usize].push(Action {
+            id: "ice".to_string(),
+            name: "Ice".to_string(),
+            ..Default::default()
+        });
+        Character {
+            growth: vec![level(40, 1, 5), third, level(180, 3, 15)],
+            cmd_available: vec![Commands::Attack],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn goes_through_every_level_reached() {
+        let mut chara = chara();
+        let gains = chara.gain_exp(120);
+
+        assert_eq!((gains.from_level, gains.to_level), (1, 3));
+        assert_eq!(chara.level, 3);
+        assert_eq!(chara.exp, 120);
+        assert_eq!(gains.stats.attack, 3);
+        assert_eq!(chara.stats.attack, 3);
+        assert_eq!(
+            (gains.health, chara.max_health, chara.health),
+            (15, 115, 115)
+        );
+        assert_eq!(gains.learnt.len(), 1);
+        assert!(chara.cmd_available.contains(&Commands::Magic));
+    }
+
+    #[test]
+    fn adds_up_over_several_battles() {
+        let mut chara = chara();
+        assert!(!chara.gain_exp(30).leveled_up());
+        let gains = chara.gain_exp(30);
+        assert_eq!((gains.from_level, gains.to_level), (1, 2));
+
+        let gains = chara.gain_exp(1000);
+        assert_eq!((gains.from_level, gains.to_level), (2, 4));
+        // There's nothing past the last entry of `growth`
+        assert!(chara.next_level().is_none());
+        assert!(!chara.gain_exp(1000).leveled_up());
+    }
+
+    #[test]
+    fn saturates_on_huge_growth() {
+        let mut chara = Character {
+            growth: vec![level(1, u16::MAX, u16::MAX)],
+            ..Default::default()
+        };
+        chara.gain_exp(u32::MAX);
+        chara.gain_exp(u32::MAX);
+        assert_eq!(chara.stats.attack, u16::MAX);
+        assert_eq!(chara.max_health, u16::MAX);
+        assert_eq!(chara.exp, u32::MAX);
+    }
+}
@@ -1,19 +1,68 @@
-use crate::BattleState;
-use rand::random;
+mod act_map;
+mod effect;
+mod element;
+mod growth;
+mod status;
+
+pub use effect::*;
+pub use element::*;
+pub use growth::*;
+use serde::{Deserialize, Serialize};
+pub use status::*;
 use std::{
+    collections::BTreeMap,
     fmt::{self, Display},
     vec,
 };
 
-#[derive(Debug, Default, Clone)]
+/// Time a character needs to accumulate before being able to act, unless
+/// its file gives it a `max_time` of its own
+pub const MAX_TIME: f32 = 60.0;
+/// How much of its defense a guarding character adds as damage reduction
+pub const GUARD_FACTOR: f32 = 1.5;
+/// Charge the Manif meter needs before its actions can be used
+pub const MANIF_CHARGE: f32 = 100.0;
+/// Manif charge per point of damage taken
+pub const MANIF_TAKEN_RATE: f32 = 2.0;
+/// Manif charge per point of damage dealt
+pub const MANIF_DEALT_RATE: f32 = 1.5;
+/// Extra Manif charge per point of hope, as a fraction of the base charge
+pub const MANIF_HOPE_BONUS: f32 = 0.1;
+/// Chance of an action to land when it doesn't set its own `accuracy`
+pub const DEFAULT_ACCURACY: f32 = 0.95;
+/// Crit chance of a character without hope
+pub const BASE_CRIT_CHANCE: f32 = 0.05;
+/// Extra crit chance per point of hope
+pub const CRIT_PER_HOPE: f32 = 0.02;
+pub const MAX_CRIT_CHANCE: f32 = 0.5;
+/// Chance to dodge an action per point of hope
+pub const EVASION_PER_HOPE: f32 = 0.015;
+pub const MAX_EVASION: f32 = 0.3;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct Stats {
     pub attack: u16,
     pub defense: u16,
     pub hope: u16,
 }
 // dmg = tu_attack * (rand * tu_hope) - enemy_defense * (rand * enemy_hope)
+impl Stats {
+    /// Chance of the character's damage to be critical
+    pub fn crit_chance(&self) -> f32 {
+        (BASE_CRIT_CHANCE + self.hope as f32 * CRIT_PER_HOPE).min(MAX_CRIT_CHANCE)
+    }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+    /// Chance of the character to dodge an action that would have landed
+    pub fn evasion(&self) -> f32 {
+        (self.hope as f32 * EVASION_PER_HOPE).min(MAX_EVASION)
+    }
+}
+
+fn default_accuracy() -> f32 {
+    DEFAULT_ACCURACY
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Commands {
     Attack,
     Defend,
@@ -27,24 +76,77 @@ impl Display for Commands {
         write!(f, "{:?}", self)
     }
 }
+impl Commands {
+    /// Every command, in the same order used to index `act_available`
+    pub const ALL: [Commands; Commands::Max as usize] = [
+        Commands::Attack,
+        Commands::Defend,
+        Commands::Magic,
+        Commands::Ability,
+        Commands::Manif,
+    ];
+}
+
+/// Who an action can be used on, relative to whoever uses it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TargetKind {
+    #[default]
+    SingleEnemy,
+    SingleAlly,
+    /// The character using the action
+    User,
+    AllEnemies,
+    AllAllies,
+    /// Any standing enemy, picked when the action is resolved
+    RandomEnemy,
+}
+impl TargetKind {
+    /// Whether the target has to be chosen when queueing the action
+    pub fn is_single(self) -> bool {
+        matches!(self, TargetKind::SingleEnemy | TargetKind::SingleAlly)
+    }
+}
 
-// ! From file
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Action {
-    name: String,
-    damage: u16,
-    duration: f32,
-    time_cost: f32,
-    mana_cost: u16,
+    /// Key of the action in the skill database
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    pub damage: u16,
+    pub duration: f32,
+    pub time_cost: f32,
+    pub mana_cost: u16,
+    /// Inflicted on the target for `duration` seconds
+    #[serde(default)]
+    pub status: Option<StatusSpec>,
+    #[serde(default)]
+    pub target: TargetKind,
+    /// Applied in order to every target the action doesn't miss
+    #[serde(default)]
+    pub effects: Vec<Effect>,
+    /// Id of the element of its damage, if any
+    #[serde(default)]
+    pub element: Option<String>,
+    /// Chance to land before the target gets to dodge, above 1.0 making up
+    /// for its evasion
+    #[serde(default = "default_accuracy")]
+    pub accuracy: f32,
 }
 impl Default for Action {
     fn default() -> Self {
         Action {
+            id: String::new(),
             name: "Action".to_string(),
             damage: 0,
             duration: 0.,
-            time_cost: 0.,
+            time_cost: MAX_TIME,
             mana_cost: 0,
+            status: None,
+            target: TargetKind::default(),
+            effects: vec![],
+            element: None,
+            accuracy: DEFAULT_ACCURACY,
         }
     }
 }
@@ -53,9 +155,44 @@ impl Display for Action {
         write!(f, "{}", self.name)
     }
 }
+impl Action {
+    /// An action only known by its id, until it's looked up in the skill database
+    pub fn reference(id: String) -> Action {
+        Action {
+            id,
+            name: String::new(),
+            ..Default::default()
+        }
+    }
+
+    pub fn is_reference(&self) -> bool {
+        self.name.is_empty()
+    }
+
+    /// Affinity of `chara` to the element of the action
+    pub fn affinity_of(&self, chara: &Character) -> Option<Affinity> {
+        chara.affinities.get(self.element.as_ref()?).copied()
+    }
+
+    /// Revives can only be used on knocked out targets
+    pub fn revives(&self) -> bool {
+        self.effects.iter().any(|e| matches!(e, Effect::Revive(_)))
+    }
+
+    pub fn heals(&self) -> bool {
+        self.effects.iter().any(|e| matches!(e, Effect::Heal(_)))
+    }
+}
+
+/// Damage reduction from defending, lasting until the character's next turn
+/// or until `remaining` seconds go by, whichever comes first
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Guard {
+    pub reduction: u16,
+    pub remaining: f32,
+}
 
-// ! From file
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Character {
     pub name: String,
     pub stats: Stats,
@@ -64,11 +201,44 @@ pub struct Character {
     pub max_health: u16,
     pub mana: u16,
     pub max_mana: u16,
+    #[serde(default)]
     pub time: f32,
+    /// Size of the time gauge, the character being ready once it's full
+    #[serde(default = "default_max_time")]
+    pub max_time: f32,
     pub time_mod: f32,
+    /// Every command but Manif, which only depends on the Manif meter
     pub cmd_available: Vec<Commands>,
+    #[serde(with = "act_map")]
     pub act_available: [Vec<Action>; Commands::Max as usize],
+    #[serde(skip)]
+    pub guard: Option<Guard>,
+    #[serde(skip)]
+    pub statuses: Vec<StatusEffect>,
+    /// Manif meter, from 0 to `MANIF_CHARGE`
+    #[serde(default)]
+    pub manif: f32,
+    /// How the character takes each element, by element id
+    #[serde(default)]
+    pub affinities: BTreeMap<String, Affinity>,
+    #[serde(default = "default_level")]
+    pub level: u16,
+    /// Total experience, see `growth` for what it takes to level up
+    #[serde(default)]
+    pub exp: u32,
+    #[serde(default)]
+    pub growth: Vec<LevelUp>,
+    /// Experience every surviving member of the other party gets when it's defeated
+    #[serde(default)]
+    pub exp_reward: u32,
 }
+fn default_max_time() -> f32 {
+    MAX_TIME
+}
+fn default_level() -> u16 {
+    1
+}
+
 impl Display for Character {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.name)
@@ -85,7 +255,8 @@ impl Default for Character {
             mana: 100,
             max_mana: 100,
             time: 0.0,
-            time_mod: 1.0 + (random::<f32>() % 2.0),
+            max_time: MAX_TIME,
+            time_mod: 1.0,
             cmd_available: vec![Commands::Attack, Commands::Defend, Commands::Ability],
             act_available: [
                 // Attack
@@ -99,37 +270,387 @@ impl Default for Character {
                 // Manifestations
                 vec![Action::default()],
             ],
+            guard: None,
+            statuses: vec![],
+            manif: 0.0,
+            affinities: BTreeMap::new(),
+            level: 1,
+            exp: 0,
+            growth: vec![],
+            exp_reward: 0,
         };
         chara.health = chara.max_health;
         chara.mana = chara.max_mana;
-        return chara;
+        chara
     }
 }
 impl Character {
-    pub fn update(&mut self, delta: f32) {
-        let mut time = self.time + delta * self.time_mod;
-        if time > 60.0 {
-            time = 60.0;
+    /// Fills the time gauge and ticks every status, returning what they did
+    pub fn update(&mut self, delta: f32) -> Vec<StatusChange> {
+        // Knocked out characters have their gauge frozen at 0
+        if self.is_ko() {
+            self.time = 0.0;
+            self.guard = None;
+            self.manif = 0.0;
+            return self
+                .statuses
+                .drain(..)
+                .map(|s| StatusChange::Removed(s.kind))
+                .collect();
         }
-        self.time = time;
+        if !self.has_status(StatusKind::Stun) {
+            let time = self.time + delta * self.effective_time_mod();
+            self.time = time.min(self.max_time);
+        }
+
+        if let Some(guard) = &mut self.guard {
+            guard.remaining -= delta;
+            if guard.remaining <= 0.0 || self.is_ready() {
+                self.guard = None;
+            }
+        }
+
+        self.tick_statuses(delta)
     }
 
-    // To add an action to the player
-    pub fn add_action(&mut self, act: &Commands) {
-        // If it already is in the actions vector, do nothing
-        if self.cmd_available.contains(act) {
-            return;
+    fn tick_statuses(&mut self, delta: f32) -> Vec<StatusChange> {
+        let mut changes = vec![];
+        for i in 0..self.statuses.len() {
+            let StatusEffect { kind, potency, .. } = self.statuses[i];
+            self.statuses[i].remaining -= delta;
+            if !matches!(kind, StatusKind::Poison | StatusKind::Regen) {
+                continue;
+            }
+
+            self.statuses[i].tick += delta;
+            while self.statuses[i].tick >= STATUS_TICK && !self.is_ko() {
+                self.statuses[i].tick -= STATUS_TICK;
+                let amount = if kind == StatusKind::Poison {
+                    let amount = potency.min(self.health);
+                    self.health -= amount;
+                    amount
+                } else {
+                    let amount = potency.min(self.max_health.saturating_sub(self.health));
+                    self.health += amount;
+                    amount
+                };
+                changes.push(StatusChange::Tick { kind, amount });
+            }
         }
-        // Else push the action and sort the vector
-        self.cmd_available.push(*act);
-        self.cmd_available.sort_unstable_by_key(|a| *a as usize);
+        self.statuses.retain(|s| {
+            if s.remaining > 0.0 {
+                return true;
+            }
+            changes.push(StatusChange::Removed(s.kind));
+            false
+        });
+        changes
+    }
+
+    pub fn has_status(&self, kind: StatusKind) -> bool {
+        self.statuses.iter().any(|s| s.kind == kind)
+    }
+
+    fn potency(&self, kind: StatusKind) -> u16 {
+        self.statuses
+            .iter()
+            .find(|s| s.kind == kind)
+            .map_or(0, |s| s.potency)
+    }
+
+    /// Applies a status following its stacking rules, returning any status it cancelled
+    pub fn apply_status(&mut self, spec: StatusSpec, duration: f32) -> Option<StatusKind> {
+        if let Some(opposite) = spec.kind.opposite() {
+            if self.has_status(opposite) {
+                self.statuses.retain(|s| s.kind != opposite);
+                return Some(opposite);
+            }
+        }
+
+        match self.statuses.iter_mut().find(|s| s.kind == spec.kind) {
+            Some(status) => {
+                status.potency = if spec.kind.stacks() {
+                    status
+                        .potency
+                        .saturating_add(spec.potency)
+                        .min(MAX_STACK_POTENCY)
+                } else {
+                    status.potency.max(spec.potency)
+                };
+                status.remaining = status.remaining.max(duration);
+            }
+            None => self.statuses.push(StatusEffect {
+                kind: spec.kind,
+                potency: spec.potency,
+                remaining: duration,
+                tick: 0.0,
+            }),
+        }
+        None
+    }
+
+    /// Stats after every buff and debuff
+    pub fn effective_stats(&self) -> Stats {
+        Stats {
+            attack: self
+                .stats
+                .attack
+                .saturating_add(self.potency(StatusKind::AttackUp))
+                .saturating_sub(self.potency(StatusKind::AttackDown)),
+            defense: self
+                .stats
+                .defense
+                .saturating_add(self.potency(StatusKind::DefenseUp))
+                .saturating_sub(self.potency(StatusKind::DefenseDown)),
+            hope: self.stats.hope,
+        }
+    }
+
+    pub fn effective_time_mod(&self) -> f32 {
+        let percent =
+            self.potency(StatusKind::Haste) as f32 - self.potency(StatusKind::Slow) as f32;
+        (self.time_mod * (1.0 + percent / 100.0)).max(0.1)
+    }
+
+    /// Starts guarding with `act`, a duration of 0 meaning until the next turn
+    pub fn defend(&mut self, act: &Action) {
+        self.guard = Some(Guard {
+            reduction: (self.effective_stats().defense as f32 * GUARD_FACTOR).round() as u16,
+            remaining: if act.duration > 0.0 {
+                act.duration
+            } else {
+                f32::INFINITY
+            },
+        });
+    }
+
+    /// Only characters with a full time gauge can act
+    pub fn is_ready(&self) -> bool {
+        !self.is_ko() && self.time >= self.max_time
+    }
+
+    pub fn is_ko(&self) -> bool {
+        self.health == 0
+    }
+
+    pub fn spend_time(&mut self, cost: f32) {
+        self.time = (self.time - cost).max(0.0);
+    }
+
+    pub fn can_afford(&self, act: &Action) -> bool {
+        self.mana >= act.mana_cost
+    }
+
+    pub fn spend_mana(&mut self, cost: u16) {
+        self.mana = self.mana.saturating_sub(cost);
     }
-}
 
-pub fn update_chars_time(state: &mut BattleState, delta: f32) {
-    for party in vec![&mut state.player_party, &mut state.enemy_party].iter_mut() {
-        for chara in party.iter_mut() {
-            chara.update(delta);
+    /// Applies the effect, returning what it did or `None` if it did nothing.
+    /// Only `Revive` has any effect on knocked out characters
+    pub fn apply_effect(&mut self, effect: Effect) -> Option<EffectOutcome> {
+        if self.is_ko() != matches!(effect, Effect::Revive(_)) {
+            return None;
         }
+        let outcome = match effect {
+            Effect::Heal(amount) => {
+                let amount = amount.min(self.max_health.saturating_sub(self.health));
+                self.health += amount;
+                EffectOutcome::Healed(amount)
+            }
+            Effect::RestoreMana(amount) => {
+                let amount = amount.min(self.max_mana.saturating_sub(self.mana));
+                self.mana += amount;
+                EffectOutcome::ManaRestored(amount)
+            }
+            Effect::Revive(health) => {
+                self.health = health.clamp(1, self.max_health.max(1));
+                EffectOutcome::Revived(self.health)
+            }
+            Effect::Cleanse => {
+                let removed = self
+                    .statuses
+                    .iter()
+                    .map(|s| s.kind)
+                    .filter(|kind| kind.is_harmful())
+                    .collect::<Vec<StatusKind>>();
+                if removed.is_empty() {
+                    return None;
+                }
+                self.statuses.retain(|s| !s.kind.is_harmful());
+                EffectOutcome::Cleansed(removed)
+            }
+            Effect::GrantTime(secs) => {
+                let before = self.time;
+                self.time = (self.time + secs).clamp(0.0, self.max_time);
+                EffectOutcome::TimeGranted(self.time - before)
+            }
+        };
+        Some(outcome)
+    }
+
+    /// Commands shown to the player, Manif being there as long as the
+    /// character has any Manif action, charged or not
+    pub fn commands(&self) -> Vec<Commands> {
+        let mut cmds = self.cmd_available.clone();
+        cmds.retain(|c| *c != Commands::Manif);
+        if !self.act_available[Commands::Manif as usize].is_empty() {
+            cmds.push(Commands::Manif);
+        }
+        cmds
+    }
+
+    pub fn manif_ready(&self) -> bool {
+        self.manif >= MANIF_CHARGE
+    }
+
+    /// Fills the Manif meter from `amount` points of damage at the given rate,
+    /// returning whether it just got full
+    pub fn charge_manif(&mut self, amount: u16, rate: f32) -> bool {
+        if self.is_ko() || self.manif_ready() {
+            return false;
+        }
+        let hope = 1.0 + self.stats.hope as f32 * MANIF_HOPE_BONUS;
+        self.manif = (self.manif + amount as f32 * rate * hope).min(MANIF_CHARGE);
+        self.manif_ready()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(kind: StatusKind, potency: u16) -> StatusSpec {
+        StatusSpec { kind, potency }
+    }
+
+    fn status(chara: &Character, kind: StatusKind) -> Option<&StatusEffect> {
+        chara.statuses.iter().find(|s| s.kind == kind)
+    }
+
+    #[test]
+    fn stacking_statuses_add_up_to_the_cap() {
+        let mut chara = Character::default();
+        chara.apply_status(spec(StatusKind::Poison, 10), 3.0);
+        chara.apply_status(spec(StatusKind::Poison, 15), 5.0);
+        let poison = status(&chara, StatusKind::Poison).unwrap();
+        assert_eq!(poison.potency, 25);
+        assert_eq!(poison.remaining, 5.0);
+
+        chara.apply_status(spec(StatusKind::Poison, 15), 1.0);
+        let poison = status(&chara, StatusKind::Poison).unwrap();
+        assert_eq!(poison.potency, MAX_STACK_POTENCY);
+        assert_eq!(poison.remaining, 5.0);
+    }
+
+    #[test]
+    fn other_statuses_keep_the_strongest() {
+        let mut chara = Character::default();
+        chara.apply_status(spec(StatusKind::AttackUp, 3), 5.0);
+        chara.apply_status(spec(StatusKind::AttackUp, 2), 8.0);
+        let buff = status(&chara, StatusKind::AttackUp).unwrap();
+        assert_eq!((buff.potency, buff.remaining), (3, 8.0));
+        assert_eq!(chara.statuses.len(), 1);
+    }
+
+    #[test]
+    fn opposites_cancel_each_other() {
+        let mut chara = Character::default();
+        chara.apply_status(spec(StatusKind::Haste, 20), 5.0);
+        let cancelled = chara.apply_status(spec(StatusKind::Slow, 20), 5.0);
+        assert_eq!(cancelled, Some(StatusKind::Haste));
+        assert!(chara.statuses.is_empty());
+    }
+
+    #[test]
+    fn huge_values_from_data_files_saturate() {
+        let mut chara = Character {
+            stats: Stats {
+                attack: u16::MAX,
+                defense: u16::MAX,
+                hope: 0,
+            },
+            ..Default::default()
+        };
+        chara.apply_status(spec(StatusKind::AttackUp, 10), 5.0);
+        chara.apply_status(spec(StatusKind::DefenseUp, 10), 5.0);
+        let stats = chara.effective_stats();
+        assert_eq!((stats.attack, stats.defense), (u16::MAX, u16::MAX));
+
+        chara.apply_status(spec(StatusKind::Poison, u16::MAX), 5.0);
+        chara.apply_status(spec(StatusKind::Poison, u16::MAX), 5.0);
+        let poison = status(&chara, StatusKind::Poison).unwrap();
+        assert_eq!(poison.potency, MAX_STACK_POTENCY);
+    }
+
+    #[test]
+    fn guard_follows_defense_buffs_and_debuffs() {
+        let guard_of = |status: Option<StatusSpec>| {
+            let mut chara = Character {
+                stats: Stats {
+                    defense: 10,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            if let Some(spec) = status {
+                chara.apply_status(spec, 5.0);
+            }
+            chara.defend(&Action::default());
+            chara.guard.unwrap().reduction
+        };
+        assert_eq!(guard_of(None), 15);
+        assert_eq!(guard_of(Some(spec(StatusKind::DefenseUp, 4))), 21);
+        assert_eq!(guard_of(Some(spec(StatusKind::DefenseDown, 4))), 9);
+    }
+
+    #[test]
+    fn effects_are_capped_at_the_maximum() {
+        let mut chara = Character {
+            health: 90,
+            mana: 95,
+            time: 50.0,
+            ..Default::default()
+        };
+        assert_eq!(
+            chara.apply_effect(Effect::Heal(30)),
+            Some(EffectOutcome::Healed(10))
+        );
+        assert_eq!(
+            chara.apply_effect(Effect::RestoreMana(30)),
+            Some(EffectOutcome::ManaRestored(5))
+        );
+        assert_eq!(
+            chara.apply_effect(Effect::GrantTime(30.0)),
+            Some(EffectOutcome::TimeGranted(10.0))
+        );
+        assert_eq!((chara.health, chara.mana, chara.time), (100, 100, MAX_TIME));
+    }
+
+    #[test]
+    fn revive_only_works_on_knocked_out_characters() {
+        let mut chara = Character::default();
+        assert_eq!(chara.apply_effect(Effect::Revive(30)), None);
+        assert_eq!(chara.health, 100);
+
+        chara.health = 0;
+        // Nothing else works on them
+        assert_eq!(chara.apply_effect(Effect::Heal(30)), None);
+        assert_eq!(
+            chara.apply_effect(Effect::Revive(300)),
+            Some(EffectOutcome::Revived(100))
+        );
+    }
+
+    #[test]
+    fn cleanse_only_removes_harmful_statuses() {
+        let mut chara = Character::default();
+        assert_eq!(chara.apply_effect(Effect::Cleanse), None);
+        chara.apply_status(spec(StatusKind::Poison, 5), 5.0);
+        chara.apply_status(spec(StatusKind::Regen, 5), 5.0);
+        assert_eq!(
+            chara.apply_effect(Effect::Cleanse),
+            Some(EffectOutcome::Cleansed(vec![StatusKind::Poison]))
+        );
+        assert!(chara.has_status(StatusKind::Regen));
     }
 }
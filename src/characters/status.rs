@@ -0,0 +1,83 @@
+use serde::{Deserialize, Serialize};
+
+/// Most potency a stacking status can build up to
+pub const MAX_STACK_POTENCY: u16 = 30;
+/// Seconds between each tick of poison and regen
+pub const STATUS_TICK: f32 = 1.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StatusKind {
+    /// Loses `potency` health every tick
+    Poison,
+    /// Recovers `potency` health every tick
+    Regen,
+    /// `time_mod` raised by `potency` percent
+    Haste,
+    /// `time_mod` lowered by `potency` percent
+    Slow,
+    /// The time gauge doesn't fill at all
+    Stun,
+    AttackUp,
+    AttackDown,
+    DefenseUp,
+    DefenseDown,
+}
+impl StatusKind {
+    /// Applying a status removes its opposite instead of stacking with it
+    pub fn opposite(self) -> Option<StatusKind> {
+        match self {
+            StatusKind::Haste => Some(StatusKind::Slow),
+            StatusKind::Slow => Some(StatusKind::Haste),
+            StatusKind::AttackUp => Some(StatusKind::AttackDown),
+            StatusKind::AttackDown => Some(StatusKind::AttackUp),
+            StatusKind::DefenseUp => Some(StatusKind::DefenseDown),
+            StatusKind::DefenseDown => Some(StatusKind::DefenseUp),
+            StatusKind::Poison | StatusKind::Regen | StatusKind::Stun => None,
+        }
+    }
+
+    /// Statuses removed by a cleanse
+    pub fn is_harmful(self) -> bool {
+        matches!(
+            self,
+            StatusKind::Poison
+                | StatusKind::Slow
+                | StatusKind::Stun
+                | StatusKind::AttackDown
+                | StatusKind::DefenseDown
+        )
+    }
+
+    /// Stacking statuses add up their potency, the rest just keep the strongest one
+    pub fn stacks(self) -> bool {
+        matches!(self, StatusKind::Poison | StatusKind::Regen)
+    }
+}
+
+/// Status an action inflicts on its target, lasting the action's duration
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct StatusSpec {
+    pub kind: StatusKind,
+    pub potency: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StatusEffect {
+    pub kind: StatusKind,
+    pub potency: u16,
+    /// Seconds left before it wears off
+    pub remaining: f32,
+    /// Seconds accumulated towards the next tick
+    pub tick: f32,
+}
+
+/// Something a status did to its character while updating
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StatusChange {
+    /// Health lost to poison or recovered by regen
+    Tick {
+        kind: StatusKind,
+        amount: u16,
+    },
+    Removed(StatusKind),
+}
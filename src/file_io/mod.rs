@@ -1,3 +1,486 @@
 mod translation;
 
+pub use translation::*;
 
+use crate::{
+    battle::{CharaId, Input, Replay, Rules, Side},
+    characters::{Action, Character, Commands, Element},
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::{self, Display},
+    fs, io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+pub const BATTLE_DIR: &str = "resources/battle";
+pub const ENCOUNTERS_DIR: &str = "resources/battle/encounters";
+pub const SKILLS_FILE: &str = "resources/battle/skills.json";
+pub const ELEMENTS_FILE: &str = "resources/battle/elements.json";
+pub const LOGS_DIR: &str = "logs";
+pub const REPLAYS_DIR: &str = "replays";
+pub const SAVES_DIR: &str = "saves";
+
+/// Error while reading a resource file, always naming the file it came from
+#[derive(Debug)]
+pub struct LoadError {
+    pub path: PathBuf,
+    pub msg: String,
+}
+impl Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.msg)
+    }
+}
+impl Error for LoadError {}
+impl LoadError {
+    pub fn new(path: &Path, msg: impl ToString) -> LoadError {
+        LoadError {
+            path: path.to_path_buf(),
+            msg: msg.to_string(),
+        }
+    }
+}
+
+pub fn read_json<T: DeserializeOwned>(path: &Path) -> Result<T, LoadError> {
+    let text = fs::read_to_string(path).map_err(|err| LoadError::new(path, err))?;
+    // Tracking the path lets the message name the field that went wrong
+    let mut de = serde_json::Deserializer::from_str(&text);
+    let value =
+        serde_path_to_error::deserialize(&mut de).map_err(|err| LoadError::new(path, err))?;
+    de.end().map_err(|err| LoadError::new(path, err))?;
+    Ok(value)
+}
+
+/// Every element actions and affinities can refer to
+pub struct ElementDb {
+    pub elements: Vec<Element>,
+}
+impl ElementDb {
+    pub fn load(path: &Path) -> Result<ElementDb, LoadError> {
+        let elements: Vec<Element> = read_json(path)?;
+        for (i, elem) in elements.iter().enumerate() {
+            if elem.id.is_empty() {
+                return Err(LoadError::new(
+                    path,
+                    format!("element `{}` has no id", elem.name),
+                ));
+            }
+            if elements[..i].iter().any(|e| e.id == elem.id) {
+                return Err(LoadError::new(
+                    path,
+                    format!("duplicate element `{}`", elem.id),
+                ));
+            }
+        }
+        Ok(ElementDb { elements })
+    }
+
+    /// Fails naming `field` if `id` isn't a known element
+    fn check(&self, path: &Path, field: &str, id: &str) -> Result<(), LoadError> {
+        if self.elements.iter().any(|e| e.id == id) {
+            Ok(())
+        } else {
+            Err(LoadError::new(
+                path,
+                format!("field `{}`: unknown element `{}`", field, id),
+            ))
+        }
+    }
+}
+
+/// Every action of the game, defined once and referenced by id, along with
+/// the elements they and the characters may refer to
+pub struct SkillDb {
+    pub skills: HashMap<String, Action>,
+    pub elements: ElementDb,
+}
+impl SkillDb {
+    pub fn load(path: &Path, elements: ElementDb) -> Result<SkillDb, LoadError> {
+        let list: Vec<Action> = read_json(path)?;
+        let mut skills = HashMap::new();
+        for act in list {
+            if act.id.is_empty() {
+                return Err(LoadError::new(path, format!("skill `{}` has no id", act)));
+            }
+            if skills.contains_key(&act.id) {
+                return Err(LoadError::new(
+                    path,
+                    format!("duplicate skill `{}`", act.id),
+                ));
+            }
+            if let Some(element) = &act.element {
+                elements.check(path, "element", element)?;
+            }
+            skills.insert(act.id.clone(), act);
+        }
+        Ok(SkillDb { skills, elements })
+    }
+
+    /// Replaces every action referenced by id with its definition, and checks
+    /// the elements of the rest
+    fn resolve_actions(
+        &self,
+        path: &Path,
+        acts: &mut [Vec<Action>; Commands::Max as usize],
+    ) -> Result<(), LoadError> {
+        for (cmd, acts) in Commands::ALL.iter().zip(acts.iter_mut()) {
+            for act in acts.iter_mut() {
+                if act.is_reference() {
+                    *act = self.skills.get(&act.id).cloned().ok_or_else(|| {
+                        LoadError::new(path, format!("unknown skill `{}` in `{}`", act.id, cmd))
+                    })?;
+                } else if let Some(element) = &act.element {
+                    self.elements.check(path, "element", element)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Resolves the actions of the character, including the ones it learns
+    /// by leveling up, and checks its affinities and growth
+    fn resolve(&self, path: &Path, chara: &mut Character) -> Result<(), LoadError> {
+        self.resolve_actions(path, &mut chara.act_available)?;
+        for level in chara.growth.iter_mut() {
+            self.resolve_actions(path, &mut level.unlocks)?;
+        }
+        for element in chara.affinities.keys() {
+            self.elements.check(path, "affinities", element)?;
+        }
+        for (i, pair) in chara.growth.windows(2).enumerate() {
+            if pair[1].exp <= pair[0].exp {
+                return Err(LoadError::new(
+                    path,
+                    format!(
+                        "field `growth`: level {} doesn't need more experience than level {}",
+                        i + 3,
+                        i + 2
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn load_character(path: &Path, skills: &SkillDb) -> Result<Character, LoadError> {
+    let mut chara = read_json(path)?;
+    skills.resolve(path, &mut chara)?;
+    Ok(chara)
+}
+
+/// Progression of a player character kept between battles. Only the
+/// experience is saved, levels being rebuilt from the character's `growth`
+#[derive(Serialize, Deserialize)]
+struct SaveFile {
+    exp: u32,
+}
+
+/// Save of the character loaded from `path`, named after its file
+fn save_path(path: &Path) -> PathBuf {
+    Path::new(SAVES_DIR).join(path.file_name().unwrap_or(path.as_os_str()))
+}
+
+/// Writes the progression of the character loaded from `path` inside `SAVES_DIR`
+pub fn save_character(path: &Path, chara: &Character) -> io::Result<PathBuf> {
+    fs::create_dir_all(SAVES_DIR)?;
+    let save = save_path(path);
+    let file = SaveFile { exp: chara.exp };
+    fs::write(&save, serde_json::to_string_pretty(&file)?)?;
+    Ok(save)
+}
+
+/// Brings the character loaded from `path` up to its saved progression, if any
+pub fn load_progress(path: &Path, chara: &mut Character) -> Result<(), LoadError> {
+    let save = save_path(path);
+    if save.is_file() {
+        let file: SaveFile = read_json(&save)?;
+        chara.gain_exp(file.exp.saturating_sub(chara.exp));
+    }
+    Ok(())
+}
+
+/// Every `.json` file inside `dir`, sorted by name
+fn party_files(dir: &Path) -> Result<Vec<PathBuf>, LoadError> {
+    let mut paths = fs::read_dir(dir)
+        .map_err(|err| LoadError::new(dir, err))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect::<Vec<PathBuf>>();
+    paths.sort();
+
+    if paths.is_empty() {
+        return Err(LoadError::new(dir, "no characters found"));
+    }
+    Ok(paths)
+}
+
+/// An encounter as written in its file, parties being paths relative to
+/// `BATTLE_DIR` that point either to a character file or to a directory of them
+#[derive(Deserialize)]
+struct EncounterFile {
+    title: String,
+    player_party: Vec<String>,
+    enemy_party: Vec<String>,
+    #[serde(default)]
+    rules: Rules,
+}
+
+/// Everything needed to start a battle
+#[derive(Clone)]
+pub struct Encounter {
+    pub title: String,
+    pub player_party: Vec<Character>,
+    /// File each player character was loaded from, to save its progression
+    pub player_files: Vec<PathBuf>,
+    pub enemy_party: Vec<Character>,
+    pub rules: Rules,
+}
+
+/// Files of every character of a party, in order
+fn party_refs(path: &Path, refs: &[String]) -> Result<Vec<PathBuf>, LoadError> {
+    let mut files = vec![];
+    for r in refs {
+        let chara_path = Path::new(BATTLE_DIR).join(r);
+        if chara_path.is_dir() {
+            files.extend(party_files(&chara_path)?);
+        } else {
+            files.push(chara_path);
+        }
+    }
+    if files.is_empty() {
+        return Err(LoadError::new(path, "empty party"));
+    }
+    Ok(files)
+}
+
+/// Loads an encounter either by id (its file name inside `ENCOUNTERS_DIR`)
+/// or by the path to its file
+pub fn load_encounter(id_or_path: &str) -> Result<Encounter, LoadError> {
+    let path = Path::new(id_or_path);
+    let path = if path.is_file() {
+        path.to_path_buf()
+    } else {
+        Path::new(ENCOUNTERS_DIR).join(format!("{}.json", id_or_path))
+    };
+
+    let file: EncounterFile = read_json(&path)?;
+    let limits = file.rules.party_limits;
+    if limits.min == 0 || limits.min > limits.max {
+        return Err(LoadError::new(
+            &path,
+            format!(
+                "field `party_limits`: invalid range {}..={}",
+                limits.min, limits.max
+            ),
+        ));
+    }
+
+    let elements = ElementDb::load(Path::new(ELEMENTS_FILE))?;
+    let skills = SkillDb::load(Path::new(SKILLS_FILE), elements)?;
+    let player_files = party_refs(&path, &file.player_party)?;
+    let encounter = Encounter {
+        title: file.title,
+        player_party: player_files
+            .iter()
+            .map(|chara| load_character(chara, &skills))
+            .collect::<Result<_, _>>()?,
+        player_files,
+        enemy_party: party_refs(&path, &file.enemy_party)?
+            .iter()
+            .map(|chara| load_character(chara, &skills))
+            .collect::<Result<_, _>>()?,
+        rules: file.rules,
+    };
+    for (field, party) in [
+        ("player_party", &encounter.player_party),
+        ("enemy_party", &encounter.enemy_party),
+    ] {
+        if !limits.allows(party.len()) {
+            return Err(LoadError::new(
+                &path,
+                format!(
+                    "field `{}`: {} characters, expected {} to {}",
+                    field,
+                    party.len(),
+                    limits.min,
+                    limits.max
+                ),
+            ));
+        }
+    }
+    Ok(encounter)
+}
+
+/// Path of a new file inside `dir`, named after the current time
+fn timestamped_path(dir: &str, ext: &str) -> io::Result<PathBuf> {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    fs::create_dir_all(dir)?;
+    Ok(Path::new(dir).join(format!("battle-{}.{}", secs, ext)))
+}
+
+/// Writes the lines of a battle log to a new file inside `LOGS_DIR`
+pub fn export_log(lines: &[String]) -> io::Result<PathBuf> {
+    let path = timestamped_path(LOGS_DIR, "txt")?;
+    fs::write(&path, lines.join("\n") + "\n")?;
+    Ok(path)
+}
+
+/// Writes a replay to a new file inside `REPLAYS_DIR`
+pub fn save_replay(replay: &Replay) -> io::Result<PathBuf> {
+    let path = timestamped_path(REPLAYS_DIR, "json")?;
+    fs::write(&path, serde_json::to_string_pretty(replay)?)?;
+    Ok(path)
+}
+
+/// Loads a replay, making sure every input points to someone in the parties
+/// since the file may have been edited
+pub fn load_replay(path: &Path) -> Result<Replay, LoadError> {
+    let replay: Replay = read_json(path)?;
+    let exists = |id: CharaId| match id.side {
+        Side::Player => id.index < replay.player_party.len(),
+        Side::Enemy => id.index < replay.enemy_party.len(),
+    };
+    for (i, recorded) in replay.inputs.iter().enumerate() {
+        if let Input::Submit(act) = &recorded.input {
+            if let Some(id) = [act.from, act.to].into_iter().find(|id| !exists(*id)) {
+                return Err(LoadError::new(
+                    path,
+                    format!(
+                        "field `inputs`: input {} points to {:?} {}, who isn't in the party",
+                        i, id.side, id.index
+                    ),
+                ));
+            }
+        }
+    }
+    Ok(replay)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    /// Writes `text` to a file of the system's temporary directory
+    fn temp_file(name: &str, text: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("battle_rpg_{}_{}", process::id(), name));
+        fs::write(&path, text).unwrap();
+        path
+    }
+
+    fn encounter_error(path: &Path) -> LoadError {
+        match load_encounter(path.to_str().unwrap()) {
+            Ok(_) => panic!("{} loaded", path.display()),
+            Err(err) => err,
+        }
+    }
+
+    #[test]
+    fn errors_name_the_field() {
+        let path = temp_file(
+            "bad_field.json",
+            r#"{ "name": "Bad", "max_health": "lots" }"#,
+        );
+        let err = read_json::<Character>(&path).unwrap_err();
+        assert_eq!(err.path, path);
+        assert!(err.msg.starts_with("max_health: "), "{}", err.msg);
+
+        let path = temp_file(
+            "bad_action.json",
+            r#"{ "act_available": { "Attack": [{ "name": "Hit", "cost": -1 }] } }"#,
+        );
+        let err = read_json::<Character>(&path).unwrap_err();
+        assert!(
+            err.msg.starts_with("act_available: `Attack` action 0: "),
+            "{}",
+            err.msg
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_party_limits() {
+        for limits in [r#"{ "min": 0, "max": 2 }"#, r#"{ "min": 3, "max": 2 }"#] {
+            let path = temp_file(
+                "bad_limits.json",
+                &format!(
+                    r#"{{
+                        "title": "Bad",
+                        "player_party": ["charas/chara1.json"],
+                        "enemy_party": ["enemies/enemy1.json"],
+                        "rules": {{ "party_limits": {} }}
+                    }}"#,
+                    limits
+                ),
+            );
+            let err = encounter_error(&path);
+            assert!(err.msg.starts_with("field `party_limits`"), "{}", err.msg);
+        }
+    }
+
+    fn skills() -> SkillDb {
+        let elements = ElementDb::load(Path::new(ELEMENTS_FILE)).unwrap();
+        SkillDb::load(Path::new(SKILLS_FILE), elements).unwrap()
+    }
+
+    #[test]
+    fn rejects_duplicate_skills() {
+        let skill = r#"{ "id": "twice", "name": "Twice", "damage": 1, "duration": 0.0, "time_cost": 1.0, "mana_cost": 0 }"#;
+        let path = temp_file("duplicate_skills.json", &format!("[{0}, {0}]", skill));
+        let elements = ElementDb::load(Path::new(ELEMENTS_FILE)).unwrap();
+        let err = match SkillDb::load(&path, elements) {
+            Ok(_) => panic!("duplicate skills loaded"),
+            Err(err) => err,
+        };
+        assert_eq!(err.msg, "duplicate skill `twice`");
+    }
+
+    #[test]
+    fn rejects_unknown_skills() {
+        let mut chara: serde_json::Value =
+            read_json(Path::new("resources/battle/charas/chara1.json")).unwrap();
+        chara["act_available"]["Magic"] = serde_json::json!(["fire", "nope"]);
+        let path = temp_file("unknown_skill.json", &chara.to_string());
+        let err = load_character(&path, &skills()).unwrap_err();
+        assert_eq!(err.msg, "unknown skill `nope` in `Magic`");
+    }
+
+    #[test]
+    fn parties_must_fit_the_limits() {
+        let path = temp_file(
+            "big_party.json",
+            r#"{
+                "title": "Crowded",
+                "player_party": ["charas"],
+                "enemy_party": ["enemies/enemy1.json"],
+                "rules": { "party_limits": { "min": 1, "max": 2 } }
+            }"#,
+        );
+        let err = encounter_error(&path);
+        assert_eq!(
+            err.msg,
+            "field `player_party`: 4 characters, expected 1 to 2"
+        );
+
+        let path = temp_file(
+            "small_party.json",
+            r#"{
+                "title": "Lonely",
+                "player_party": ["charas/chara1.json", "charas/chara2.json"],
+                "enemy_party": ["enemies/enemy1.json"],
+                "rules": { "party_limits": { "min": 2, "max": 4 } }
+            }"#,
+        );
+        let err = encounter_error(&path);
+        assert_eq!(
+            err.msg,
+            "field `enemy_party`: 1 characters, expected 2 to 4"
+        );
+    }
+}
@@ -0,0 +1,105 @@
+use super::{read_json, LoadError};
+use std::{collections::HashMap, env, path::Path};
+
+pub const LANG_DIR: &str = "resources/lang";
+/// Language used for any string missing in the chosen one
+pub const DEFAULT_LOCALE: &str = "es";
+
+/// String tables of the chosen locale and of the default one
+pub struct Translation {
+    pub locale: String,
+    strings: HashMap<String, String>,
+    fallback: HashMap<String, String>,
+}
+impl Translation {
+    /// Loads `locale`, falling back entirely to the default locale if it has no file
+    pub fn load(locale: &str) -> Result<Translation, LoadError> {
+        let fallback: HashMap<String, String> =
+            read_json(&Path::new(LANG_DIR).join(format!("{}.json", DEFAULT_LOCALE)))?;
+
+        let path = Path::new(LANG_DIR).join(format!("{}.json", locale));
+        let (locale, strings) = if locale != DEFAULT_LOCALE && path.is_file() {
+            (locale.to_string(), read_json(&path)?)
+        } else {
+            (DEFAULT_LOCALE.to_string(), HashMap::new())
+        };
+
+        Ok(Translation {
+            locale,
+            strings,
+            fallback,
+        })
+    }
+
+    /// Locale taken from the `LANG` environment variable, e.g. `en` for `en_US.UTF-8`
+    pub fn system_locale() -> Option<String> {
+        let lang = env::var("LANG").ok()?;
+        let locale = lang.split(['_', '.']).next()?;
+        (!locale.is_empty() && locale != "C").then(|| locale.to_lowercase())
+    }
+
+    pub fn get_opt(&self, key: &str) -> Option<&str> {
+        self.strings
+            .get(key)
+            .or_else(|| self.fallback.get(key))
+            .map(|s| s.as_str())
+    }
+
+    /// The text for `key`, or the key itself when no table has it
+    pub fn get<'a>(&'a self, key: &'a str) -> &'a str {
+        self.get_opt(key).unwrap_or(key)
+    }
+
+    /// Like `get`, replacing `{0}`, `{1}`... with the given arguments
+    pub fn fmt(&self, key: &str, args: &[&dyn ToString]) -> String {
+        let mut text = self.get(key).to_string();
+        for (i, arg) in args.iter().enumerate() {
+            text = text.replace(&format!("{{{}}}", i), &arg.to_string());
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(entries: &[(&str, &str)]) -> HashMap<String, String> {
+        entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn missing_strings_come_from_the_default_locale() {
+        let lang = Translation {
+            locale: "en".to_string(),
+            strings: table(&[("ui.log", "Log")]),
+            fallback: table(&[("ui.log", "Registro"), ("ui.from", "¿Quién?")]),
+        };
+        assert_eq!(lang.get("ui.log"), "Log");
+        assert_eq!(lang.get("ui.from"), "¿Quién?");
+        assert_eq!(lang.get("ui.nothing"), "ui.nothing");
+        assert_eq!(lang.get_opt("ui.nothing"), None);
+    }
+
+    #[test]
+    fn unknown_locales_fall_back_entirely() {
+        let lang = Translation::load("xx").unwrap();
+        assert_eq!(lang.locale, DEFAULT_LOCALE);
+        let en = Translation::load("en").unwrap();
+        assert_eq!(en.locale, "en");
+        assert_ne!(en.get("cmd.Magic"), lang.get("cmd.Magic"));
+    }
+
+    #[test]
+    fn fills_in_the_arguments() {
+        let lang = Translation {
+            locale: "en".to_string(),
+            strings: table(&[("log.ko", "{0} is down, {1} left")]),
+            fallback: HashMap::new(),
+        };
+        assert_eq!(lang.fmt("log.ko", &[&"Enemy", &2]), "Enemy is down, 2 left");
+    }
+}
@@ -0,0 +1,5 @@
+//! Battle engine, independent from any interface
+
+pub mod battle;
+pub mod characters;
+pub mod file_io;
@@ -1,18 +1,19 @@
-mod characters;
-mod file_io;
 mod ui_rendering;
 
 // Importing
-use characters::*;
+use battle_rpg::{battle::*, file_io::*};
 use crossterm::{
     event::{self, Event as CEvent},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use std::{
+    env,
     error::Error,
     io,
     option::Option::*,
+    path::{Path, PathBuf},
+    process,
     sync::mpsc,
     thread,
     time::{Duration, Instant},
@@ -21,170 +22,145 @@ use tui::{
     backend::{Backend, CrosstermBackend},
     Terminal,
 };
-use ui_rendering::{StatefulList, UiState};
+use ui_rendering::UiState;
 
-fn get_initial_parties() -> (Vec<Character>, Vec<Character>) {
-    let enemy_party = vec![
-        Character {
-            name: "Enemigo".to_string(),
-            stats: Stats {
-                attack: 5,
-                defense: 5,
-                hope: 2,
-            },
-            health: 23,
-            max_health: 100,
-            mana: 82,
-            max_mana: 100,
-            ..Default::default()
-        },
-        Character {
-            name: "Enemigo2".to_string(),
-            stats: Stats {
-                attack: 5,
-                defense: 5,
-                hope: 2,
-            },
-            health: 23,
-            max_health: 100,
-            mana: 82,
-            max_mana: 100,
-            ..Default::default()
-        },
-        Character {
-            name: "Enemigo3".to_string(),
-            stats: Stats {
-                attack: 5,
-                defense: 5,
-                hope: 2,
-            },
-            health: 23,
-            max_health: 100,
-            mana: 82,
-            max_mana: 100,
-            ..Default::default()
-        },
-        Character {
-            name: "Enemigo4".to_string(),
-            stats: Stats {
-                attack: 5,
-                defense: 5,
-                hope: 2,
-            },
-            health: 23,
-            max_health: 100,
-            mana: 82,
-            max_mana: 100,
-            ..Default::default()
-        },
-    ];
-    let player_party = vec![
-        Character {
-            name: "Personaje1".to_string(),
-            stats: Stats {
-                attack: 5,
-                defense: 4,
-                hope: 3,
-            },
-            health: 78,
-            max_health: 100,
-            mana: 45,
-            max_mana: 100,
-            ..Default::default()
-        },
-        Character {
-            name: "Personaje2".to_string(),
-            stats: Stats {
-                attack: 3,
-                defense: 5,
-                hope: 4,
-            },
-            health: 83,
-            max_health: 100,
-            mana: 56,
-            max_mana: 100,
-            ..Default::default()
-        },
-        Character {
-            name: "Personaje3".to_string(),
-            stats: Stats {
-                attack: 3,
-                defense: 4,
-                hope: 5,
-            },
-            health: 27,
-            max_health: 100,
-            mana: 38,
-            max_mana: 100,
-            ..Default::default()
-        },
-        Character {
-            name: "Personaje4".to_string(),
-            stats: Stats {
-                attack: 3,
-                defense: 4,
-                hope: 5,
-            },
-            health: 27,
-            max_health: 100,
-            mana: 38,
-            max_mana: 100,
-            ..Default::default()
-        },
-    ];
-    return (enemy_party, player_party);
+/// Options given through the command line
+pub struct Args {
+    /// Id or path of the encounter to fight
+    pub encounter: String,
+    /// `random` or `heuristic`
+    pub ai: String,
+    /// Language of the interface, taken from `LANG` if not given
+    pub lang: String,
+    /// Seed of the battle, random if not given
+    pub seed: u64,
+    /// Replay file to play instead of fighting
+    pub replay: Option<String>,
 }
+impl Args {
+    pub fn parse() -> Args {
+        let mut args = Args {
+            encounter: "default".to_string(),
+            ai: "heuristic".to_string(),
+            lang: Translation::system_locale().unwrap_or_else(|| DEFAULT_LOCALE.to_string()),
+            seed: rand::random(),
+            replay: None,
+        };
+        let mut iter = env::args().skip(1);
+        while let Some(arg) = iter.next() {
+            if arg == "--ai" {
+                if let Some(ai) = iter.next() {
+                    args.ai = ai;
+                }
+            } else if arg == "--lang" {
+                if let Some(lang) = iter.next() {
+                    args.lang = lang;
+                }
+            } else if arg == "--seed" {
+                match iter.next().map(|seed| seed.parse()) {
+                    Some(Ok(seed)) => args.seed = seed,
+                    _ => {
+                        eprintln!("--seed expects a positive integer");
+                        process::exit(1);
+                    }
+                }
+            } else if arg == "--replay" {
+                args.replay = iter.next();
+            } else {
+                args.encounter = arg;
+            }
+        }
+        args
+    }
 
-pub struct Game<'a> {
-    pub app_state: AppState<'a>,
-    pub battle_state: BattleState,
+    pub fn enemy_ai(&self) -> Box<dyn EnemyController> {
+        enemy_controller(&self.ai).unwrap_or_else(|| Box::new(HeuristicController::default()))
+    }
+}
+
+pub struct Game {
+    pub app_state: AppState,
+    pub engine: Engine,
     pub ui_state: UiState,
+    /// Set when watching a replay instead of fighting
+    pub playback: Option<Playback>,
+    /// Files the player party was loaded from, empty when watching a replay
+    pub player_files: Vec<PathBuf>,
 }
-impl<'a> Game<'a> {
-    pub fn new(title: &'a str) -> Game<'a> {
-        let (enemy_party, player_party) = get_initial_parties();
+impl Game {
+    pub fn new(
+        encounter: &str,
+        lang: &str,
+        enemy_ai: Box<dyn EnemyController>,
+        seed: u64,
+    ) -> Result<Game, LoadError> {
+        let mut encounter = load_encounter(encounter)?;
+        // Only the game carries progression over, simulations stick to the files
+        for (path, chara) in encounter
+            .player_files
+            .iter()
+            .zip(encounter.player_party.iter_mut())
+        {
+            load_progress(path, chara)?;
+        }
+        let lang = Translation::load(lang)?;
 
-        Game {
+        Ok(Game {
             app_state: AppState {
-                tittle: title,
+                tittle: encounter.title.clone(),
                 should_quit: false,
             },
-            // ! From file
-            battle_state: BattleState {
-                enemy_party,
-                player_party,
-            },
-            ui_state: UiState {
-                enemy_party: None,
-                player_party: None,
-                // ! From file
-                from: StatefulList::with_items(vec!["".to_string()], "Quien?"),
-                what: StatefulList::with_items(vec!["".to_string()], "Qué?"),
-                which: StatefulList::with_items(vec!["".to_string()], "Cual?"),
-                to: StatefulList::with_items(vec!["".to_string()], "A quien?"),
+            ui_state: UiState::new(lang),
+            player_files: encounter.player_files.clone(),
+            engine: Engine::from_encounter(encounter, enemy_ai, seed),
+            playback: None,
+        })
+    }
+
+    pub fn from_replay(path: &str, lang: &str) -> Result<Game, LoadError> {
+        let path = Path::new(path);
+        let replay = load_replay(path)?;
+        let lang = Translation::load(lang)?;
+        let engine = Engine::from_replay(&replay)
+            .ok_or_else(|| LoadError::new(path, format!("unknown ai `{}`", replay.ai)))?;
+
+        Ok(Game {
+            app_state: AppState {
+                tittle: replay.title,
+                should_quit: false,
             },
-        }
+            ui_state: UiState::new(lang),
+            engine,
+            playback: Some(Playback::new(replay.inputs)),
+            player_files: vec![],
+        })
     }
 }
 
-pub struct AppState<'a> {
-    pub tittle: &'a str,
+pub struct AppState {
+    pub tittle: String,
     pub should_quit: bool,
 }
 
-pub struct BattleState {
-    /// Max 4
-    enemy_party: Vec<Character>,
-    /// Max 4
-    player_party: Vec<Character>,
-}
-
 enum Event<I> {
     Input(I),
     Tick,
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    let game = match &args.replay {
+        Some(path) => Game::from_replay(path, &args.lang),
+        None => Game::new(&args.encounter, &args.lang, args.enemy_ai(), args.seed),
+    };
+    let mut game = match game {
+        Ok(game) => game,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+    };
+
     // setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -192,9 +168,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    // create app and run it
-    let game = Game::new("Asies");
-    let res = run_app(&mut terminal, game);
+    // run app
+    let res = run_app(&mut terminal, &mut game);
 
     // restore terminal
     disable_raw_mode()?;
@@ -204,11 +179,28 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     if let Err(err) = res {
         println!("{:?}", err)
     }
+    // So the battle can be played again with `--seed` or `--replay`
+    println!("seed: {}", game.engine.state().seed);
+    if game.playback.is_none() {
+        match save_replay(game.engine.replay()) {
+            Ok(path) => println!("replay: {}", path.display()),
+            Err(err) => eprintln!("couldn't save the replay: {}", err),
+        }
+    }
+    // Progression only carries over from battles actually won
+    if game.engine.outcome() == Some(Outcome::Victory) {
+        let party = &game.engine.state().player_party;
+        for (path, chara) in game.player_files.iter().zip(party) {
+            if let Err(err) = save_character(path, chara) {
+                eprintln!("couldn't save {}: {}", chara.name, err);
+            }
+        }
+    }
 
     Ok(())
 }
 
-fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut game: Game) -> Result<(), Box<dyn Error>> {
+fn run_app<B: Backend>(terminal: &mut Terminal<B>, game: &mut Game) -> Result<(), Box<dyn Error>> {
     //* Event loop
     let (tx, rx) = mpsc::channel();
     let tick_rate = Duration::from_millis(200);
@@ -236,18 +228,31 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut game: Game) -> Result<(),
     while !game.app_state.should_quit {
         let time = Instant::now();
 
-        update_chars_time(&mut game.battle_state, delta);
-        game.ui_state.populate(&game.battle_state);
+        match &mut game.playback {
+            Some(playback) => {
+                playback.advance(&mut game.engine, delta);
+                game.ui_state.populate_playback(playback);
+            }
+            None => game.engine.advance(delta),
+        }
+        game.ui_state.populate(game.engine.state());
 
         //* Render job
-        ui_rendering::draw(terminal, &mut game.ui_state)?;
+        ui_rendering::draw(terminal, &game.app_state, &mut game.ui_state)?;
 
         //* Event handler
         match rx.recv()? {
-            Event::Input(event) => {
-                game.ui_state
-                    .handle_events(&mut game.app_state, &mut game.battle_state, event)
-            }
+            Event::Input(event) => match &mut game.playback {
+                Some(playback) => game.ui_state.handle_playback_events(
+                    &mut game.app_state,
+                    &mut game.engine,
+                    playback,
+                    event,
+                ),
+                None => game
+                    .ui_state
+                    .handle_events(&mut game.app_state, &mut game.engine, event),
+            },
             Event::Tick => {}
         }
 
@@ -1,51 +1,95 @@
-use crate::{characters::Character,
-    ui_rendering::*};
+use crate::ui_rendering::*;
+use battle_rpg::{
+    battle::{CharaId, Outcome, Side},
+    characters::{Character, Commands, MANIF_CHARGE},
+    file_io::Translation,
+};
 // use core::option::{Option::None, Option::Some};
 use tui::{
     backend::Backend,
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::*,
-    // text::Span,
+    text::{Span, Spans},
     widgets::*,
     Frame,
     // Terminal,
 };
 
+/// Narrowest a character panel gets before the party wraps into another row
+const MIN_PANEL_WIDTH: u16 = 24;
+/// Lines taken by the gauges and the name of a character
+const PANEL_HEIGHT: u16 = 4;
+/// A party never takes more rows than this, however narrow the terminal
+const MAX_PARTY_ROWS: usize = 2;
 
-pub fn build_enemies_section<B: Backend>(rect: &mut Frame<B>, party: &Vec<Character>, chunk: &Rect) {
-    let constraints = {
-        let p_len = party.len();
-        vec![Constraint::Percentage((100 / p_len) as u16); p_len]
-    };
+/// Rows needed to fit `count` character panels in `width` columns
+fn party_rows(width: u16, count: usize) -> usize {
+    let per_row = (width.saturating_sub(2) / MIN_PANEL_WIDTH).max(1) as usize;
+    count.div_ceil(per_row).clamp(1, MAX_PARTY_ROWS)
+}
 
-    let party_chunks = Layout::default()
-        .direction(Direction::Horizontal)
+/// Height of a party section, borders included
+pub fn party_height(width: u16, count: usize) -> u16 {
+    party_rows(width, count) as u16 * PANEL_HEIGHT + 2
+}
+
+/// Splits a party section into one panel per character, row by row
+fn party_slots(chunk: &Rect, count: usize) -> Vec<Rect> {
+    if count == 0 {
+        return vec![];
+    }
+    let rows = party_rows(chunk.width, count);
+    let per_row = count.div_ceil(rows);
+    Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints(vec![Constraint::Length(PANEL_HEIGHT); rows])
+        .split(*chunk)
+        .iter()
+        .flat_map(|row| {
+            Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints(vec![Constraint::Ratio(1, per_row as u32); per_row])
+                .split(*row)
+        })
+        .take(count)
+        .collect()
+}
+
+/// The four lines of a character panel
+fn panel_lines(slot: Rect) -> Vec<Rect> {
+    Layout::default()
+        .direction(Direction::Vertical)
         .horizontal_margin(1)
-        .constraints(constraints)
-        .split(*chunk);
+        .constraints(vec![Constraint::Length(1); PANEL_HEIGHT as usize])
+        .split(slot)
+}
 
-    for (i, e_chunk) in party_chunks.iter().enumerate() {
-        let enemy = &party[i];
-        let char_chunks = Layout::default()
-            .direction(Direction::Vertical)
-            .margin(1)
-            .constraints(
-                [
-                    Constraint::Percentage(25),
-                    Constraint::Percentage(25),
-                    Constraint::Percentage(25),
-                    Constraint::Percentage(25),
-                ]
-                .as_ref(),
-            )
-            .split(*e_chunk);
+/// Latest flash on the character, if any
+fn flash_of(flashes: &[(CharaId, Flash)], side: Side, index: usize) -> Option<Flash> {
+    flashes
+        .iter()
+        .find(|(id, _)| id.side == side && id.index == index)
+        .map(|(_, flash)| *flash)
+}
+
+pub fn build_enemies_section<B: Backend>(
+    rect: &mut Frame<B>,
+    party: &[Character],
+    flashes: &[(CharaId, Flash)],
+    chunk: &Rect,
+    lang: &Translation,
+) {
+    let slots = party_slots(chunk, party.len());
+    for (index, (enemy, slot)) in party.iter().zip(slots).enumerate() {
+        let flash = flash_of(flashes, Side::Enemy, index);
+        let char_chunks = panel_lines(slot);
 
         let gauge = create_gauge(
             enemy.mana as f32,
             enemy.max_mana as f32,
             Color::Cyan,
-            // ! From file
-            "Mana",
+            lang.get("gauge.mana"),
             Option::Some(Modifier::BOLD),
             true,
         );
@@ -55,28 +99,19 @@ pub fn build_enemies_section<B: Backend>(rect: &mut Frame<B>, party: &Vec<Charac
             enemy.health as f32,
             enemy.max_health as f32,
             Color::Red,
-            // ! From file
-            "Health",
+            lang.get("gauge.health"),
             Option::Some(Modifier::BOLD),
             true,
         );
         rect.render_widget(gauge, char_chunks[1]);
 
-        let p_name = Paragraph::new(enemy.name.clone())
-            .style(Style::default())
+        let p_name = Paragraph::new(name_text(enemy, flash, lang))
+            .style(name_style(enemy, flash))
             .block(Block::default().borders(Borders::LEFT | Borders::RIGHT))
             .alignment(Alignment::Center);
         rect.render_widget(p_name, char_chunks[2]);
 
-        let gauge = create_gauge(
-            enemy.time,
-            1.0f32,
-            Color::Green,
-            // ! From file
-            "Time",
-            Option::Some(Modifier::SLOW_BLINK),
-            true,
-        );
+        let gauge = create_time_gauge(enemy, lang, true);
         rect.render_widget(gauge, char_chunks[3]);
     }
 }
@@ -105,14 +140,47 @@ pub fn build_middle_panels<B: Backend>(rect: &mut Frame<B>, state: &mut UiState,
     render_statefull_list(rect, &mut state.which, &middle_chunks[2]);
     // To who list
     render_statefull_list(rect, &mut state.to, &middle_chunks[3]);
+
+    // Battle log
+    build_log_panel(rect, state, &middle_chunks[4]);
+}
+
+pub fn build_log_panel<B: Backend>(rect: &mut Frame<B>, state: &UiState, chunk: &Rect) {
+    // Only the entries that fit, ending `log_scroll` entries before the latest
+    let visible = chunk.height.saturating_sub(2) as usize;
+    let end = state.log_lines.len().saturating_sub(state.log_scroll);
+    let start = end.saturating_sub(visible);
+    let items = state.log_lines[start..end]
+        .iter()
+        .map(|line| ListItem::new(line.clone()))
+        .collect::<Vec<ListItem>>();
+
+    let mut title = state.lang.get("ui.log").to_string();
+    if let Some(status) = &state.log_status {
+        title += &format!(" - {}", status);
+    }
+    let log = List::new(items).block(Block::default().title(title).borders(Borders::all()));
+    rect.render_widget(log, *chunk);
 }
 
-pub fn render_statefull_list<B: Backend>(rect: &mut Frame<B>, s_list: &mut StatefulList, chunk: &Rect) {
+pub fn render_statefull_list<B: Backend>(
+    rect: &mut Frame<B>,
+    s_list: &mut StatefulList,
+    chunk: &Rect,
+) {
     let list = List::new(
         s_list
             .items
             .iter()
-            .map(|s| ListItem::new(s.clone()))
+            .enumerate()
+            .map(|(i, s)| {
+                let item = ListItem::new(s.clone());
+                if s_list.is_disabled(i) {
+                    item.style(Style::default().fg(Color::DarkGray))
+                } else {
+                    item
+                }
+            })
             .collect::<Vec<ListItem>>(),
     )
     .block(
@@ -130,47 +198,23 @@ pub fn render_statefull_list<B: Backend>(rect: &mut Frame<B>, s_list: &mut State
     rect.render_stateful_widget(list, *chunk, &mut s_list.state);
 }
 
-pub fn build_characters_section<B: Backend>(rect: &mut Frame<B>, party: &Vec<Character>, chunk: &Rect) {
-    let constraints = {
-        let p_len = party.len();
-        vec![Constraint::Percentage((100 / p_len) as u16); p_len]
-    };
+pub fn build_characters_section<B: Backend>(
+    rect: &mut Frame<B>,
+    party: &[Character],
+    flashes: &[(CharaId, Flash)],
+    chunk: &Rect,
+    lang: &Translation,
+) {
+    let slots = party_slots(chunk, party.len());
+    for (index, (player, slot)) in party.iter().zip(slots).enumerate() {
+        let flash = flash_of(flashes, Side::Player, index);
+        let char_chunks = panel_lines(slot);
 
-    let party_chunks = Layout::default()
-        .direction(Direction::Horizontal)
-        .horizontal_margin(1)
-        .constraints(constraints)
-        .split(*chunk);
-
-    for (i, c_chunk) in party_chunks.iter().enumerate() {
-        let player = &party[i];
-        let char_chunks = Layout::default()
-            .direction(Direction::Vertical)
-            .margin(1)
-            .constraints(
-                [
-                    Constraint::Percentage(25),
-                    Constraint::Percentage(25),
-                    Constraint::Percentage(25),
-                    Constraint::Percentage(25),
-                ]
-                .as_ref(),
-            )
-            .split(*c_chunk);
-
-        let gauge = create_gauge(
-            player.time,
-            1.0f32,
-            Color::Green,
-            // ! From file
-            "Time",
-            Option::Some(Modifier::SLOW_BLINK),
-            false,
-        );
+        let gauge = create_time_gauge(player, lang, false);
         rect.render_widget(gauge, char_chunks[0]);
 
-        let p_name = Paragraph::new(player.name.clone())
-            .style(Style::default())
+        let p_name = Paragraph::new(name_text(player, flash, lang))
+            .style(name_style(player, flash))
             .block(Block::default().borders(Borders::LEFT | Borders::RIGHT))
             .alignment(Alignment::Center);
         rect.render_widget(p_name, char_chunks[1]);
@@ -179,8 +223,7 @@ pub fn build_characters_section<B: Backend>(rect: &mut Frame<B>, party: &Vec<Cha
             player.health as f32,
             player.max_health as f32,
             Color::Red,
-            // ! From file
-            "Health",
+            lang.get("gauge.health"),
             Option::Some(Modifier::BOLD),
             false,
         );
@@ -190,8 +233,7 @@ pub fn build_characters_section<B: Backend>(rect: &mut Frame<B>, party: &Vec<Cha
             player.mana as f32,
             player.max_mana as f32,
             Color::Cyan,
-            // ! From file
-            "Mana",
+            lang.get("gauge.mana"),
             Option::Some(Modifier::BOLD),
             false,
         );
@@ -199,6 +241,85 @@ pub fn build_characters_section<B: Backend>(rect: &mut Frame<B>, party: &Vec<Cha
     }
 }
 
+/// Name of the character followed by its Manif meter, guard and statuses,
+/// or by the hit it just took while it flashes
+fn name_text(chara: &Character, flash: Option<Flash>, lang: &Translation) -> String {
+    let mut text = chara.name.clone();
+    if let Some(flash) = flash {
+        return format!("{} {}", text, lang.get(&format!("flash.{:?}", flash)));
+    }
+    if chara.commands().contains(&Commands::Manif) {
+        text += &format!(
+            " [{} {}%]",
+            lang.get("gauge.manif"),
+            percent_of(chara.manif, MANIF_CHARGE)
+        );
+    }
+    if chara.guard.is_some() {
+        text += &format!(" [{}]", lang.get("status.guard"));
+    }
+    for status in chara.statuses.iter() {
+        text += &format!(" {}", status_name(lang, status.kind));
+    }
+    text
+}
+
+fn name_style(chara: &Character, flash: Option<Flash>) -> Style {
+    if let Some(flash) = flash {
+        let color = match flash {
+            Flash::Crit => Color::Yellow,
+            Flash::Miss | Flash::Dodge => Color::Gray,
+        };
+        Style::default()
+            .fg(Color::Black)
+            .bg(color)
+            .add_modifier(Modifier::BOLD)
+    } else if chara.is_ko() {
+        Style::default()
+            .fg(Color::DarkGray)
+            .add_modifier(Modifier::CROSSED_OUT)
+    } else if chara.guard.is_some() {
+        Style::default()
+            .fg(Color::Blue)
+            .add_modifier(Modifier::BOLD)
+    } else if chara.manif_ready() {
+        Style::default()
+            .fg(Color::Magenta)
+            .add_modifier(Modifier::BOLD)
+    } else {
+        Style::default()
+    }
+}
+
+/// Outcome of the battle followed by what the party got out of it
+pub fn build_result_screen<B: Backend>(
+    rect: &mut Frame<B>,
+    outcome: Outcome,
+    results: &[String],
+    chunk: &Rect,
+    lang: &Translation,
+) {
+    let (key, color) = match outcome {
+        Outcome::Victory => ("result.victory", Color::Green),
+        Outcome::Defeat => ("result.defeat", Color::Red),
+        Outcome::Fled => ("result.fled", Color::Yellow),
+        Outcome::TimeUp => ("result.time_up", Color::Red),
+    };
+    let mut lines = vec![
+        Spans::from(Span::styled(
+            lang.get(key),
+            Style::default().fg(color).add_modifier(Modifier::BOLD),
+        )),
+        Spans::default(),
+    ];
+    lines.extend(results.iter().map(|line| Spans::from(line.as_str())));
+    lines.push(Spans::from(lang.get("result.exit")));
+    let result = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::all()))
+        .alignment(Alignment::Center);
+    rect.render_widget(result, *chunk);
+}
+
 fn create_gauge(
     value: f32,
     max: f32,
@@ -206,14 +327,8 @@ fn create_gauge(
     name: &str,
     mods: Option<Modifier>,
     enemy: bool,
-) -> Gauge {
-    let percent: u16 = if max == 1.0 {
-        (value / 60.0 * 100.0).round()
-    }
-    // Percentage of time
-    else {
-        (value * 100.0 / max).round()
-    } as u16;
+) -> Gauge<'_> {
+    let percent = percent_of(value, max);
     Gauge::default()
         .block(Block::default().borders(Borders::LEFT | Borders::RIGHT))
         .gauge_style(
@@ -222,15 +337,45 @@ fn create_gauge(
                 .add_modifier(mods.unwrap_or(Modifier::empty())),
         )
         .percent(percent)
-        .label(if max != 1.0 {
-            if !enemy {
-                format!("{}: {}/{}", name, value as u16, max as u16)
-            } else {
-                format!("{}: {}", name, value as u16)
-            }
-        } else if !enemy {
-            format!("{}: {}/{}", name, percent, 100u16)
+        .label(if !enemy {
+            format!("{}: {}/{}", name, value as u16, max as u16)
         } else {
-            format!("{}: {}", name, percent)
+            format!("{}: {}", name, value as u16)
         })
 }
+
+/// Time gauge of the character, shown as a percentage of its `max_time`
+/// until it fills up and the character is ready
+fn create_time_gauge<'a>(chara: &Character, lang: &'a Translation, enemy: bool) -> Gauge<'a> {
+    let percent = percent_of(chara.time, chara.max_time);
+    let (label, style) = if chara.is_ready() {
+        (
+            lang.get("gauge.ready").to_string(),
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        )
+    } else if !enemy {
+        (
+            format!("{}: {}/{}", lang.get("gauge.time"), percent, 100u16),
+            Style::default().fg(Color::Green),
+        )
+    } else {
+        (
+            format!("{}: {}", lang.get("gauge.time"), percent),
+            Style::default().fg(Color::Green),
+        )
+    };
+    Gauge::default()
+        .block(Block::default().borders(Borders::LEFT | Borders::RIGHT))
+        .gauge_style(style)
+        .percent(percent)
+        .label(label)
+}
+
+fn percent_of(value: f32, max: f32) -> u16 {
+    if max <= 0.0 {
+        return 0;
+    }
+    (value * 100.0 / max).round().clamp(0.0, 100.0) as u16
+}
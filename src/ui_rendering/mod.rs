@@ -1,19 +1,67 @@
 mod battle_blocks;
 
-use crate::{characters::Character, AppState, BattleState, Commands};
+use crate::AppState;
 use battle_blocks::*;
+use battle_rpg::{
+    battle::{
+        check_command, ActionError, BattleEvent, BattleState, CharaId, Engine, LogEntry, Outcome,
+        Playback, QueuedAction, Side,
+    },
+    characters::{Action, Affinity, Character, Commands, EffectOutcome, Gains, StatusKind},
+    file_io::{export_log, Translation},
+};
 use core::option::{Option::None, Option::Some};
 use crossterm::event::{KeyCode, KeyEvent};
 use std::fmt::Display;
 use tui::{
     backend::Backend,
-    layout::{Constraint, Direction, Layout},
+    layout::{Alignment, Constraint, Direction, Layout},
     // text::Span,
     widgets::*,
     Frame,
     Terminal,
 };
 
+/// Battle seconds a crit or a miss stays flashed on the panel it hit
+const FLASH_TIME: f32 = 0.8;
+
+/// Outcome of a hit shown for a moment on the panel of its target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flash {
+    Crit,
+    Miss,
+    Dodge,
+}
+
+/// Crits and misses of the last `FLASH_TIME` seconds, latest first
+fn recent_flashes(b_state: &BattleState) -> Vec<(CharaId, Flash)> {
+    b_state
+        .log
+        .iter()
+        .rev()
+        .take_while(|entry| b_state.elapsed - entry.time < FLASH_TIME)
+        .filter_map(|entry| match &entry.event {
+            BattleEvent::Action {
+                to,
+                result: Some(result),
+                ..
+            } => {
+                let flash = if result.evaded {
+                    Flash::Dodge
+                } else if result.miss {
+                    Flash::Miss
+                } else if result.crit {
+                    Flash::Crit
+                } else {
+                    return None;
+                };
+                Some((*to, flash))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
 pub struct UiState {
     pub enemy_party: Option<Vec<Character>>,
     pub player_party: Option<Vec<Character>>,
@@ -22,34 +70,242 @@ pub struct UiState {
     pub what: StatefulList,
     pub which: StatefulList,
     pub to: StatefulList,
+    /// Who each entry of the `to` list points to
+    pub targets: Vec<CharaId>,
+    /// Battle log already formatted, oldest entry first
+    pub log_lines: Vec<String>,
+    /// How many entries the log is scrolled up from the latest one
+    pub log_scroll: usize,
+    /// Result of the last log export
+    pub log_status: Option<String>,
+    /// Set once the battle is over, to show the result screen
+    pub outcome: Option<Outcome>,
+    /// Speed and pause state, only when watching a replay
+    pub replay_status: Option<String>,
+    /// Hits flashing on the panels of their targets, latest first
+    pub flashes: Vec<(CharaId, Flash)>,
+    /// What the party got out of the battle, shown on the result screen
+    pub results: Vec<String>,
+    pub lang: Translation,
 }
 impl UiState {
+    pub fn new(lang: Translation) -> UiState {
+        UiState {
+            enemy_party: None,
+            player_party: None,
+            from: StatefulList::with_items(vec!["".to_string()], lang.get("ui.from")),
+            what: StatefulList::with_items(vec!["".to_string()], lang.get("ui.what")),
+            which: StatefulList::with_items(vec!["".to_string()], lang.get("ui.which")),
+            to: StatefulList::with_items(vec!["".to_string()], lang.get("ui.to")),
+            targets: vec![],
+            log_lines: vec![],
+            log_scroll: 0,
+            log_status: None,
+            outcome: None,
+            replay_status: None,
+            flashes: vec![],
+            results: vec![],
+            lang,
+        }
+    }
+
+    fn action_name(&self, act: &Action) -> String {
+        self.lang
+            .get_opt(&format!("skill.{}", act.id))
+            .unwrap_or(&act.name)
+            .to_string()
+    }
+
+    fn error_text(&self, err: &ActionError) -> String {
+        match err {
+            ActionError::KnockedOut => self.lang.get("error.knocked_out").to_string(),
+            ActionError::TargetDown => self.lang.get("error.target_down").to_string(),
+            ActionError::NotReady => self.lang.get("error.not_ready").to_string(),
+            ActionError::WrongTarget => self.lang.get("error.wrong_target").to_string(),
+            ActionError::ManifNotCharged => self.lang.get("error.manif_not_charged").to_string(),
+            ActionError::NotAvailable => self.lang.get("error.not_available").to_string(),
+            ActionError::NotEnoughMana { cost, mana } => {
+                self.lang.fmt("error.not_enough_mana", &[mana, cost])
+            }
+        }
+    }
+
+    /// Summary of what the character got out of the battle
+    fn gains_lines(&self, chara: &Character, gains: &Gains) -> Vec<String> {
+        let mut lines = vec![self.lang.fmt("result.exp", &[chara, &gains.exp])];
+        if gains.leveled_up() {
+            lines.push(
+                self.lang
+                    .fmt("result.level", &[&gains.from_level, &gains.to_level]),
+            );
+            lines.push(self.lang.fmt(
+                "result.stats",
+                &[
+                    &gains.stats.attack,
+                    &gains.stats.defense,
+                    &gains.stats.hope,
+                    &gains.health,
+                    &gains.mana,
+                ],
+            ));
+        }
+        if !gains.learnt.is_empty() {
+            let names = gains
+                .learnt
+                .iter()
+                .map(|(_, act)| self.action_name(act))
+                .collect::<Vec<String>>();
+            lines.push(self.lang.fmt("result.learnt", &[&names.join(", ")]));
+        }
+        lines.push(String::new());
+        lines
+    }
+
+    fn affinity_tag(&self, affinity: Affinity) -> String {
+        format!("[{}]", self.lang.get(&format!("affinity.{:?}", affinity)))
+    }
+
+    /// Name of a target, tagged with its affinity to the element of `act`
+    /// once a hit has revealed it
+    fn target_label(&self, b_state: &BattleState, id: CharaId, act: Option<&Action>) -> String {
+        let chara = b_state.chara(id);
+        let affinity = act.and_then(|act| {
+            let element = act.element.as_ref()?;
+            if b_state.is_discovered(id, element) {
+                act.affinity_of(chara)
+            } else {
+                None
+            }
+        });
+        match affinity {
+            Some(affinity) => format!("{} {}", chara, self.affinity_tag(affinity)),
+            None => chara.to_string(),
+        }
+    }
+
+    fn log_line(&self, b_state: &BattleState, entry: &LogEntry) -> String {
+        let text = match &entry.event {
+            BattleEvent::Seed(seed) => self.lang.fmt("log.seed", &[seed]),
+            BattleEvent::Action {
+                from,
+                to,
+                action,
+                result,
+            } => {
+                let mut text = format!(
+                    "{} > {}: {}",
+                    b_state.chara(*from),
+                    b_state.chara(*to),
+                    self.action_name(action)
+                );
+                match result {
+                    Some(result) if result.evaded => {
+                        text += &format!(" {}", self.lang.get("log.evaded"))
+                    }
+                    Some(result) if result.miss => {
+                        text += &format!(" {}", self.lang.get("log.miss"))
+                    }
+                    Some(result) if result.affinity == Some(Affinity::Absorb) => {
+                        text += &format!(" +{}", result.amount);
+                        text += &format!(" {}", self.lang.get("log.absorbed"));
+                    }
+                    Some(result) => {
+                        text += &format!(" -{}", result.amount);
+                        if let Some(affinity) = result.affinity {
+                            text += &format!(" {}", self.affinity_tag(affinity));
+                        }
+                        if result.crit {
+                            text += &format!(" {}", self.lang.get("log.crit"));
+                        }
+                        if result.overkill > 0 {
+                            text +=
+                                &format!(" {}", self.lang.fmt("log.overkill", &[&result.overkill]));
+                        }
+                    }
+                    None => {}
+                }
+                text
+            }
+            BattleEvent::Rejected { from, action, err } => match action {
+                Some(action) => format!(
+                    "{} ({}): {}",
+                    b_state.chara(*from),
+                    self.action_name(action),
+                    self.error_text(err)
+                ),
+                None => format!("{}: {}", b_state.chara(*from), self.error_text(err)),
+            },
+            BattleEvent::Ko(id) => self.lang.fmt("log.ko", &[b_state.chara(*id)]),
+            BattleEvent::Effect { id, outcome } => {
+                let chara = b_state.chara(*id);
+                match outcome {
+                    EffectOutcome::Healed(amount) => self.lang.fmt("log.healed", &[chara, amount]),
+                    EffectOutcome::ManaRestored(amount) => {
+                        self.lang.fmt("log.mana_restored", &[chara, amount])
+                    }
+                    EffectOutcome::Revived(health) => {
+                        self.lang.fmt("log.revived", &[chara, health])
+                    }
+                    EffectOutcome::Cleansed(kinds) => {
+                        let names = kinds
+                            .iter()
+                            .map(|kind| status_name(&self.lang, *kind))
+                            .collect::<Vec<String>>();
+                        self.lang.fmt("log.cleansed", &[chara, &names.join(" ")])
+                    }
+                    EffectOutcome::TimeGranted(secs) => self
+                        .lang
+                        .fmt("log.time_granted", &[chara, &format!("{:.1}", secs)]),
+                }
+            }
+            BattleEvent::StatusApplied(id, kind) => self.lang.fmt(
+                "log.status_applied",
+                &[b_state.chara(*id), &status_name(&self.lang, *kind)],
+            ),
+            BattleEvent::StatusTick { id, kind, amount } => self.lang.fmt(
+                if *kind == StatusKind::Poison {
+                    "log.poison"
+                } else {
+                    "log.regen"
+                },
+                &[b_state.chara(*id), amount, &status_name(&self.lang, *kind)],
+            ),
+            BattleEvent::StatusRemoved(id, kind) => self.lang.fmt(
+                "log.status_removed",
+                &[b_state.chara(*id), &status_name(&self.lang, *kind)],
+            ),
+            BattleEvent::ManifReady(id) => self.lang.fmt("log.manif_ready", &[b_state.chara(*id)]),
+            BattleEvent::FleeFailed => self.lang.get("flee.not_allowed").to_string(),
+            BattleEvent::LevelUp { id, level } => {
+                self.lang.fmt("log.level_up", &[b_state.chara(*id), level])
+            }
+        };
+        format!("[{:>5.1}] {}", entry.time, text)
+    }
+
     pub fn handle_events(
         &mut self,
         app_state: &mut AppState,
-        battle_state: &mut BattleState,
+        engine: &mut Engine,
         event: KeyEvent,
     ) {
+        if self.outcome.is_some() {
+            if let KeyCode::Enter | KeyCode::Char('q') = event.code {
+                app_state.should_quit = true;
+            }
+            return;
+        }
+
         match event.code {
             //* if Input
             KeyCode::Char('q') => {
                 app_state.should_quit = true;
-                return;
             }
-            KeyCode::Char('e') => {
-                let mut done = false;
-                for player in battle_state.player_party.iter_mut() {
-                    if done {
-                        return;
-                    }
-                    if !player.cmd_available.contains(&Commands::Magic) {
-                        player.add_action(&Commands::Magic);
-                        done = true;
-                    } else if !player.cmd_available.contains(&Commands::Manif) {
-                        player.add_action(&Commands::Manif);
-                        done = true;
-                    }
-                }
+            KeyCode::Char('f') => {
+                engine.flee();
+            }
+            KeyCode::Char('l') | KeyCode::PageUp | KeyCode::PageDown => {
+                self.handle_log_events(event.code);
             }
             KeyCode::Up | KeyCode::Char('w') => {
                 self.prev();
@@ -61,12 +317,75 @@ impl UiState {
                 self.unselect();
             }
             KeyCode::Right | KeyCode::Char('d') | KeyCode::Enter | KeyCode::Char(' ') => {
-                self.select();
+                self.select(engine);
             }
             _ => {}
         }
     }
 
+    /// Keys of the battle log, the same while fighting and watching a replay
+    fn handle_log_events(&mut self, code: KeyCode) {
+        match code {
+            KeyCode::Char('l') => {
+                self.log_status = Some(match export_log(&self.log_lines) {
+                    Ok(path) => self.lang.fmt("log.exported", &[&path.display()]),
+                    Err(err) => err.to_string(),
+                });
+            }
+            KeyCode::PageUp => {
+                self.log_scroll = (self.log_scroll + 1).min(self.log_lines.len().saturating_sub(1));
+            }
+            KeyCode::PageDown => {
+                self.log_scroll = self.log_scroll.saturating_sub(1);
+            }
+            _ => {}
+        }
+    }
+
+    /// While watching a replay the lists can't be used, only the playback controls
+    pub fn handle_playback_events(
+        &mut self,
+        app_state: &mut AppState,
+        engine: &mut Engine,
+        playback: &mut Playback,
+        event: KeyEvent,
+    ) {
+        if self.outcome.is_some() {
+            if let KeyCode::Enter | KeyCode::Char('q') = event.code {
+                app_state.should_quit = true;
+            }
+            return;
+        }
+
+        match event.code {
+            KeyCode::Char('q') => {
+                app_state.should_quit = true;
+            }
+            KeyCode::Char(' ') | KeyCode::Char('p') => {
+                playback.paused = !playback.paused;
+            }
+            KeyCode::Right | KeyCode::Char('n') => {
+                playback.paused = true;
+                playback.next_event(engine);
+            }
+            KeyCode::Char('+') => {
+                playback.faster();
+            }
+            KeyCode::Char('-') => {
+                playback.slower();
+            }
+            code => self.handle_log_events(code),
+        }
+    }
+
+    pub fn populate_playback(&mut self, playback: &Playback) {
+        let mut status = self.lang.fmt("replay.speed", &[&playback.speed()]);
+        if playback.paused {
+            status += &format!(" {}", self.lang.get("replay.paused"));
+        }
+        self.replay_status = Some(status);
+    }
+
     pub fn populate(&mut self, b_state: &BattleState) {
         let enemy_party = &b_state.enemy_party;
         let player_party = &b_state.player_party;
@@ -83,21 +402,128 @@ impl UiState {
         }
 
         self.from.change_items(player_party);
+        self.from
+            .set_disabled(player_party.iter().map(|c| !c.is_ready()).collect());
         if let Some(i) = self.from.state.selected() {
             let char = &player_party[i];
-            self.what.change_items(&char.cmd_available);
+            let commands = char.commands();
+            let cmds = commands
+                .iter()
+                .map(|cmd| self.lang.get(&format!("cmd.{:?}", cmd)).to_string())
+                .collect::<Vec<String>>();
+            self.what.change_items(&cmds);
+            self.what.set_disabled(
+                commands
+                    .iter()
+                    .map(|cmd| check_command(char, *cmd).is_err())
+                    .collect(),
+            );
 
             if let Some(i) = self.what.state.selected() {
-                let cmd = char.cmd_available[i];
-                self.which.change_items(&char.act_available[cmd as usize]);
+                let cmd = commands[i];
+                let actions = &char.act_available[cmd as usize];
+                let names = actions
+                    .iter()
+                    .map(|a| self.action_name(a))
+                    .collect::<Vec<String>>();
+                self.which.change_items(&names);
+                self.which
+                    .set_disabled(actions.iter().map(|a| !char.can_afford(a)).collect());
+            }
+        }
+        // Only the characters the highlighted action can be used on
+        let skill = self.selected_from().zip(self.selected_skill(b_state));
+        self.targets = skill.map_or(vec![], |(from, act)| b_state.target_candidates(from, act));
+        match skill {
+            // Actions without a choice of target just list who they land on
+            Some((from, act)) if !act.target.is_single() => {
+                let reach = b_state.in_reach(from, act);
+                let targets = reach
+                    .iter()
+                    .map(|id| self.target_label(b_state, *id, Some(act)))
+                    .collect::<Vec<String>>();
+                self.to.change_items(&targets);
+                self.to.set_disabled(vec![true; reach.len()]);
+                self.to.title = self
+                    .lang
+                    .get(&format!("target.{:?}", act.target))
+                    .to_string();
+            }
+            _ => {
+                let act = skill.map(|(_, act)| act);
+                let targets = self
+                    .targets
+                    .iter()
+                    .map(|id| self.target_label(b_state, *id, act))
+                    .collect::<Vec<String>>();
+                self.to.change_items(&targets);
+                self.to.title = self.lang.get("ui.to").to_string();
             }
         }
-        let targets = enemy_party
+
+        self.log_lines = b_state
+            .log
+            .iter()
+            .map(|entry| self.log_line(b_state, entry))
+            .collect();
+
+        self.flashes = recent_flashes(b_state);
+        self.results = b_state
+            .gains
             .iter()
-            .cloned()
-            .chain(player_party.iter().cloned())
-            .collect::<Vec<Character>>();
-        self.to.change_items(&targets);
+            .flat_map(|(id, gains)| self.gains_lines(b_state.chara(*id), gains))
+            .collect();
+        self.outcome = b_state.outcome();
+    }
+
+    fn selected_from(&self) -> Option<CharaId> {
+        Some(CharaId {
+            side: Side::Player,
+            index: self.from.state.selected()?,
+        })
+    }
+
+    fn selected_chara<'b>(&self, b_state: &'b BattleState) -> Option<&'b Character> {
+        b_state.player_party.get(self.from.state.selected()?)
+    }
+
+    fn selected_command(&self, b_state: &BattleState) -> Option<Commands> {
+        let chara = self.selected_chara(b_state)?;
+        chara.commands().get(self.what.state.selected()?).copied()
+    }
+
+    fn selected_skill<'b>(&self, b_state: &'b BattleState) -> Option<&'b Action> {
+        let chara = self.selected_chara(b_state)?;
+        let command = self.selected_command(b_state)?;
+        chara.act_available[command as usize].get(self.which.state.selected()?)
+    }
+
+    /// Builds the action described by the current selection of the four lists
+    fn selected_action(&self, b_state: &BattleState) -> Option<QueuedAction> {
+        let from = self.selected_from()?;
+        let command = self.selected_command(b_state)?;
+        let action = self.selected_skill(b_state)?.clone();
+        // The engine finds the targets of the actions without a choice
+        let to = if action.target.is_single() {
+            *self.targets.get(self.to.state.selected()?)?
+        } else {
+            from
+        };
+        Some(QueuedAction {
+            from,
+            command,
+            action,
+            to,
+        })
+    }
+
+    /// Queues the selected action and starts the selection over
+    fn push_selected(&mut self, engine: &mut Engine) {
+        if let Some(act) = self.selected_action(engine.state()) {
+            // Rejections end up in the battle log
+            let _ = engine.submit(act);
+        }
+        self.unselect_all();
     }
 
     fn unselect_all(&mut self) {
@@ -131,16 +557,33 @@ impl UiState {
         }
     }
 
-    pub fn select(&mut self) {
+    pub fn select(&mut self, engine: &mut Engine) {
         if !self.from.blocked && self.from.state.selected().is_some() {
+            if let Some(from) = self.selected_from() {
+                if engine.validate(from, None).is_err() {
+                    return;
+                }
+            }
             self.from.select();
         } else if !self.what.blocked && self.what.state.selected().is_some() {
             self.what.select();
         } else if !self.which.blocked && self.which.state.selected().is_some() {
-            self.which.select();
+            let Some(from) = self.selected_from() else {
+                return;
+            };
+            let Some(act) = self.selected_skill(engine.state()).cloned() else {
+                return;
+            };
+            if engine.validate(from, Some(&act)).is_err() {
+                return;
+            }
+            if act.target.is_single() {
+                self.which.select();
+            } else {
+                self.push_selected(engine);
+            }
         } else if !self.to.blocked && self.to.state.selected().is_some() {
-            self.unselect_all()
-            // TODO: Push action to the queue
+            self.push_selected(engine);
         }
     }
 
@@ -155,11 +598,18 @@ impl UiState {
     }
 }
 
+/// Short name of a status, as shown in the party panels
+pub fn status_name(lang: &Translation, kind: StatusKind) -> String {
+    lang.get(&format!("status.{:?}", kind)).to_string()
+}
+
 pub struct StatefulList {
     blocked: bool,
     title: String,
     state: ListState,
     items: Vec<String>,
+    /// Items that are shown but can't be selected
+    disabled: Vec<bool>,
 }
 impl StatefulList {
     pub fn with_items(items: Vec<String>, title: &str) -> StatefulList {
@@ -168,10 +618,14 @@ impl StatefulList {
             title: title.to_string(),
             state: ListState::default(),
             items,
+            disabled: vec![],
         }
     }
 
     pub fn next(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
         let i = match self.state.selected() {
             Some(i) => {
                 if i >= self.items.len() - 1 {
@@ -186,6 +640,9 @@ impl StatefulList {
     }
 
     pub fn prev(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
         let i = match self.state.selected() {
             Some(i) => {
                 if i == 0 {
@@ -200,6 +657,11 @@ impl StatefulList {
     }
 
     pub fn select(&mut self) {
+        if let Some(i) = self.state.selected() {
+            if self.is_disabled(i) {
+                return;
+            }
+        }
         self.blocked = true;
     }
 
@@ -210,28 +672,55 @@ impl StatefulList {
 
     pub fn change_items<C: Display + Clone>(&mut self, items: &[C]) {
         self.items = items.iter().map(|c| c.to_string()).collect::<Vec<String>>();
+        self.disabled.clear();
+        if let Some(i) = self.state.selected() {
+            if i >= self.items.len() {
+                self.state.select(self.items.len().checked_sub(1));
+            }
+        }
+    }
+
+    pub fn set_disabled(&mut self, disabled: Vec<bool>) {
+        self.disabled = disabled;
+    }
+
+    pub fn is_disabled(&self, i: usize) -> bool {
+        self.disabled.get(i).copied().unwrap_or(false)
     }
 }
 
 pub fn draw<B: Backend>(
     term: &mut Terminal<B>,
+    app_state: &AppState,
     state: &mut UiState,
 ) -> Result<(), Box<dyn std::error::Error>> {
     term.draw(|rect| {
-        term_ui(rect, state);
+        term_ui(rect, app_state, state);
     })?;
     Ok(())
 }
 
-fn term_ui<B: Backend>(rect: &mut Frame<B>, state: &mut UiState) {
+fn term_ui<B: Backend>(rect: &mut Frame<B>, app_state: &AppState, state: &mut UiState) {
     let mut size = rect.size();
-    if size.height % 2 != 0 {
-        size.height -= 1;
-    }
-    if size.width % 2 != 0 {
-        size.width -= 1;
+    size.height -= size.height % 2;
+    size.width -= size.width % 2;
+
+    //* Encounter title around everything
+    let title = match &state.replay_status {
+        Some(status) => format!("{} - {}", app_state.tittle, status),
+        None => app_state.tittle.clone(),
+    };
+    let blocko = Block::default()
+        .title(title)
+        .title_alignment(Alignment::Center);
+    rect.render_widget(blocko, size);
+
+    if let Some(outcome) = state.outcome {
+        build_result_screen(rect, outcome, &state.results, &size, &state.lang);
+        return;
     }
 
+    let party_len = |party: &Option<Vec<Character>>| party.as_ref().map_or(0, |p| p.len());
     let chunks = Layout::default()
         // * La direccion en la que se va a separar el espacio
         .direction(Direction::Vertical)
@@ -240,9 +729,9 @@ fn term_ui<B: Backend>(rect: &mut Frame<B>, state: &mut UiState) {
         //* Las restricciones de cada separacion
         .constraints(
             [
-                Constraint::Length(6),
+                Constraint::Length(party_height(size.width, party_len(&state.enemy_party))),
                 Constraint::Min(6),
-                Constraint::Length(6),
+                Constraint::Length(party_height(size.width, party_len(&state.player_party))),
             ]
             .as_ref(),
         )
@@ -250,19 +739,23 @@ fn term_ui<B: Backend>(rect: &mut Frame<B>, state: &mut UiState) {
         .split(size);
 
     //* Making enemies panel
-    let blocko = Block::default().title("Enemigos").borders(Borders::all());
+    let blocko = Block::default()
+        .title(state.lang.get("ui.enemies"))
+        .borders(Borders::all());
     rect.render_widget(blocko, chunks[0]);
     if let Some(party) = &state.enemy_party {
-        build_enemies_section(rect, party, &chunks[0]);
+        build_enemies_section(rect, party, &state.flashes, &chunks[0], &state.lang);
     }
 
     //* Making middle panels
     build_middle_panels(rect, state, &chunks[1]);
 
     //* Making player characters panel
-    let blocko = Block::default().title("Personajes").borders(Borders::all());
+    let blocko = Block::default()
+        .title(state.lang.get("ui.characters"))
+        .borders(Borders::all());
     rect.render_widget(blocko, chunks[2]);
     if let Some(party) = &state.player_party {
-        build_characters_section(rect, party, &chunks[2]);
+        build_characters_section(rect, party, &state.flashes, &chunks[2], &state.lang);
     }
 }